@@ -0,0 +1,295 @@
+// cargo bench --bench activity
+// MOB_LIMIT enemies running the game's AI systems (awareness, chasing with separation
+// and cohesion, orbiting, shooting patterns) and animation every frame, versus through
+// the activity level of detail that sleeps the far ones. Once with everyone scattered
+// over the map, once with most of them coming from the director's spawn ring and
+// chasing the player. The broadphase grid is rebuilt outside the timed part
+#[allow(dead_code)]
+#[path = "../src/activity.rs"]
+mod activity;
+#[allow(dead_code)]
+#[path = "../src/ai.rs"]
+mod ai;
+#[allow(dead_code)]
+#[path = "../src/bullet_pattern.rs"]
+mod bullet_pattern;
+#[allow(dead_code)]
+#[path = "../src/collision.rs"]
+mod collision;
+#[allow(dead_code)]
+#[path = "../src/components.rs"]
+mod components;
+#[allow(dead_code)]
+#[path = "../src/director.rs"]
+mod director;
+#[allow(dead_code)]
+#[path = "../src/flowfield.rs"]
+mod flowfield;
+#[allow(dead_code)]
+#[path = "../src/player_state.rs"]
+mod player_state;
+#[allow(dead_code)]
+#[path = "../src/projectile.rs"]
+mod projectile;
+#[allow(dead_code)]
+#[path = "../src/prop.rs"]
+mod prop;
+#[allow(dead_code)]
+#[path = "../src/render.rs"]
+mod render;
+#[allow(dead_code)]
+#[path = "../src/spatial.rs"]
+mod spatial;
+#[allow(dead_code)]
+#[path = "../src/steering.rs"]
+mod steering;
+#[allow(dead_code)]
+#[path = "../src/texturemanager.rs"]
+mod texturemanager;
+
+use std::{
+    f32::consts::TAU,
+    time::{Duration, Instant},
+};
+
+use activity::activity_dt;
+use bullet_pattern::BulletPattern;
+use components::Faction;
+use director::EnemyKind;
+use flowfield::FlowField;
+use glam::{IVec2, UVec2, Vec2};
+use projectile::Projectiles;
+use rand::{rngs::ThreadRng, Rng};
+use render::Tilemap;
+use sdl2_animation::Keyframe;
+use spatial::SpatialGrid;
+
+// render.rs reads the screen size from the crate root
+const SCREEN_WIDTH: u32 = 1280;
+const SCREEN_HEIGHT: u32 = 720;
+
+const MOB_LIMIT: usize = 320;
+const MAP_TILES: u32 = 200;
+const TILE_SIZE: u32 = 64;
+const MAP_HALF_SIZE: f32 = (MAP_TILES * TILE_SIZE / 2) as f32;
+const FRAMES: u32 = 1000;
+const DT: f32 = 1.0 / 60.0;
+// ring spawns get the director's detection radius, scattered ones the AI defaults
+const RING_DETECTION_RADIUS: f32 = 1400.0 + 128.0;
+const SPAWN_RING: (f32, f32) = (900.0, 1400.0);
+const SHOOTER_CHANCE: f64 = 0.33;
+
+struct Placement {
+    position: Vec2,
+    detection_radius: Option<f32>,
+    shooter: bool,
+}
+
+// everything the AI systems read besides the world
+struct Level {
+    tilemap: Tilemap,
+    flow_field: FlowField,
+    enemy_grid: SpatialGrid,
+    obstacle_grid: SpatialGrid,
+    projectiles: Projectiles,
+}
+
+fn level() -> Level {
+    let mut tilemap = Tilemap::new(MAP_TILES, MAP_TILES, TILE_SIZE, TILE_SIZE);
+    for x in 0..MAP_TILES as i32 {
+        for y in 0..MAP_TILES as i32 {
+            tilemap.set(IVec2::new(x, y), Some(render::GRASS));
+        }
+    }
+    // the player stands still, so the field is built once like the game does
+    let mut flow_field = FlowField::new(tilemap.width(), tilemap.height());
+    flow_field.update(&tilemap, tilemap.world_to_tile(Vec2::ZERO));
+    Level {
+        tilemap,
+        flow_field,
+        enemy_grid: SpatialGrid::new(128.0),
+        obstacle_grid: SpatialGrid::new(128.0),
+        projectiles: Projectiles::new(),
+    }
+}
+
+// the same layout for both runs so they do the same work, only the level of detail
+// run gives the enemies an Activity
+fn spawn(layout: &[Placement], level_of_detail: bool) -> hecs::World {
+    let mut world = hecs::World::new();
+    world.spawn((
+        components::Player::default(),
+        components::Transform::default(),
+        components::PlayerController::default(),
+    ));
+    let idle: Vec<Keyframe> = (0..2)
+        .map(|frame| Keyframe {
+            x: frame * 32,
+            y: 0,
+            width: 32,
+            height: 32,
+            duration: Duration::from_secs_f32(0.5),
+        })
+        .collect();
+    for placement in layout {
+        let mut animation = components::Animation::default();
+        animation.state.play(&idle);
+        let transform =
+            components::Transform::with_position(placement.position.x, placement.position.y);
+        let sprite = components::Sprite {
+            filename: "res/snake.png",
+            size: UVec2::new(64, 64),
+        };
+        let id = if placement.shooter {
+            let mut orbit_ai = components::OrbitAI::default();
+            if let Some(radius) = placement.detection_radius {
+                orbit_ai.radius_ghosting = radius;
+            }
+            let awareness = components::Awareness::with_detection_radius(orbit_ai.radius_ghosting);
+            let shooting = components::ShootingEnemy {
+                pattern: BulletPattern::Spread {
+                    count: 3,
+                    angle: 30.0,
+                },
+                ..Default::default()
+            };
+            world.spawn((
+                transform,
+                sprite,
+                orbit_ai,
+                awareness,
+                shooting,
+                components::Enemy {
+                    kind: EnemyKind::Orbit,
+                },
+                components::Collider::enemy(),
+                Faction::Snakes,
+                animation,
+            ))
+        } else {
+            let mut ghost_ai = components::GhostAI::default();
+            if let Some(radius) = placement.detection_radius {
+                ghost_ai.radius = radius;
+            }
+            let awareness = components::Awareness::with_detection_radius(ghost_ai.radius);
+            world.spawn((
+                transform,
+                sprite,
+                ghost_ai,
+                awareness,
+                components::Enemy {
+                    kind: EnemyKind::Ghost,
+                },
+                components::Collider::enemy(),
+                Faction::Snakes,
+                animation,
+            ))
+        };
+        if level_of_detail {
+            let _ = world.insert_one(id, components::Activity::default());
+        }
+    }
+    world
+}
+
+// the AI and animation systems of one game frame, returns the bullets fired
+fn frame(world: &mut hecs::World, level: &Level, rng: &mut ThreadRng) -> usize {
+    ai::system_activity(world, DT);
+    ai::system_awareness(world, &level.tilemap, &level.obstacle_grid, &[], rng, DT);
+    ai::system_ghost_ai(
+        world,
+        &level.enemy_grid,
+        &level.flow_field,
+        &level.tilemap,
+        &level.obstacle_grid,
+        DT,
+    );
+    let bullets = ai::system_shooting_enemies(world, &level.projectiles, DT).len();
+    ai::system_orbit_ai(
+        world,
+        &level.flow_field,
+        &level.tilemap,
+        &level.obstacle_grid,
+        DT,
+    );
+    ai::system_animation(world, DT);
+    bullets
+}
+
+fn measure(name: &str, layout: &[Placement], level_of_detail: bool) {
+    let mut level = level();
+    let mut world = spawn(layout, level_of_detail);
+    let mut rng = rand::thread_rng();
+    let (mut ticked, mut bullets) = (0, 0);
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..FRAMES {
+        level.enemy_grid.clear();
+        for (id, (transform, sprite, _)) in &mut world.query::<(
+            &components::Transform,
+            &components::Sprite,
+            &components::Enemy,
+        )>() {
+            level
+                .enemy_grid
+                .insert_rect(id, transform.position, sprite.size.as_vec2());
+        }
+        let start = Instant::now();
+        bullets += frame(&mut world, &level, &mut rng);
+        elapsed += start.elapsed();
+        ticked += world
+            .query_mut::<(&components::Enemy, Option<&components::Activity>)>()
+            .into_iter()
+            .filter(|(_id, (_, activity))| activity_dt(*activity, DT) > 0.0)
+            .count();
+    }
+    let per_frame = elapsed / FRAMES;
+    println!(
+        "{name:<28} {per_frame:>9.2?} per frame, {} mobs ticked per frame, {bullets} bullets",
+        ticked / FRAMES as usize
+    );
+}
+
+// `ring_share` of the enemies come from the spawn ring, the rest is scattered over the map
+fn layout(rng: &mut ThreadRng, ring_share: f32) -> Vec<Placement> {
+    let scattered = MOB_LIMIT - (MOB_LIMIT as f32 * ring_share) as usize;
+    let inside = MAP_HALF_SIZE - TILE_SIZE as f32 * 2.0;
+    (0..MOB_LIMIT)
+        .map(|index| {
+            let (position, detection_radius) = if index < scattered {
+                let position = Vec2::new(
+                    rng.gen_range(-inside..inside),
+                    rng.gen_range(-inside..inside),
+                );
+                (position, None)
+            } else {
+                let angle = rng.gen_range(0.0..TAU);
+                let distance = rng.gen_range(SPAWN_RING.0..SPAWN_RING.1);
+                (
+                    Vec2::from_angle(angle) * distance,
+                    Some(RING_DETECTION_RADIUS),
+                )
+            };
+            Placement {
+                position,
+                detection_radius,
+                shooter: rng.gen_bool(SHOOTER_CHANCE),
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    println!(
+        "{MOB_LIMIT} enemies over a {0}x{0} px map",
+        MAP_HALF_SIZE * 2.0
+    );
+    // the initial population only, nobody found the player yet
+    let scattered = layout(&mut rng, 0.0);
+    measure("scattered, every frame", &scattered, false);
+    measure("scattered, level of detail", &scattered, true);
+    // late run, most of the cap came in waves and is fighting around the player
+    let fight = layout(&mut rng, 0.75);
+    measure("fight, every frame", &fight, false);
+    measure("fight, level of detail", &fight, true);
+}
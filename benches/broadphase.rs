@@ -0,0 +1,121 @@
+// cargo bench --bench broadphase
+// bullets against MOB_LIMIT enemies (grid rebuilt every frame) and the player
+// against every dash crystal (grid built once), brute force versus spatial grid
+#[allow(dead_code)]
+#[path = "../src/spatial.rs"]
+mod spatial;
+
+use std::time::{Duration, Instant};
+
+use glam::Vec2;
+use rand::Rng;
+use spatial::SpatialGrid;
+
+const MOB_LIMIT: usize = 320;
+const BULLETS: usize = 256;
+const DASH_CRYSTALS: usize = 800;
+const MAP_HALF_SIZE: f32 = 6400.0;
+const FRAMES: u32 = 1000;
+
+struct Body {
+    id: hecs::Entity,
+    position: Vec2,
+    size: Vec2,
+}
+
+fn overlaps(a: &Body, b: &Body) -> bool {
+    a.position.x < b.position.x + b.size.x
+        && b.position.x < a.position.x + a.size.x
+        && a.position.y < b.position.y + b.size.y
+        && b.position.y < a.position.y + a.size.y
+}
+
+fn scatter(world: &mut hecs::World, count: usize, size: f32, spread: f32) -> Vec<Body> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|index| Body {
+            id: world.spawn((index,)),
+            position: Vec2::new(
+                rng.gen_range(-spread..spread),
+                rng.gen_range(-spread..spread),
+            ),
+            size: Vec2::splat(size),
+        })
+        .collect()
+}
+
+fn brute_force(targets: &[Body], queries: &[Body]) -> usize {
+    let mut hits = 0;
+    for query in queries {
+        for target in targets {
+            if overlaps(query, target) {
+                hits += 1;
+                break;
+            }
+        }
+    }
+    hits
+}
+
+fn fill(grid: &mut SpatialGrid, targets: &[Body]) {
+    grid.clear();
+    for target in targets {
+        grid.insert_rect(target.id, target.position, target.size);
+    }
+}
+
+fn grid(grid: &SpatialGrid, queries: &[Body]) -> usize {
+    let mut hits = 0;
+    for query in queries {
+        let found = grid
+            .query_rect(query.position, query.position + query.size)
+            .any(|entry| {
+                overlaps(
+                    query,
+                    &Body {
+                        id: entry.id,
+                        position: entry.min,
+                        size: entry.max - entry.min,
+                    },
+                )
+            });
+        if found {
+            hits += 1;
+        }
+    }
+    hits
+}
+
+fn measure(name: &str, mut run: impl FnMut() -> usize) -> usize {
+    let start = Instant::now();
+    let mut hits = 0;
+    for _ in 0..FRAMES {
+        hits = run();
+    }
+    let per_frame: Duration = start.elapsed() / FRAMES;
+    println!("{name:<32} {per_frame:>12.2?} per frame, {hits} hits");
+    hits
+}
+
+fn main() {
+    let mut world = hecs::World::new();
+    // enemies and bullets packed around the player like in a real fight
+    let enemies = scatter(&mut world, MOB_LIMIT, 64.0, 1024.0);
+    let bullets = scatter(&mut world, BULLETS, 32.0, 1024.0);
+    let crystals = scatter(&mut world, DASH_CRYSTALS, 80.0, MAP_HALF_SIZE);
+    let player = scatter(&mut world, 1, 80.0, 64.0);
+    let mut enemy_grid = SpatialGrid::new(128.0);
+    let mut crystal_grid = SpatialGrid::new(128.0);
+    fill(&mut crystal_grid, &crystals);
+
+    println!("{MOB_LIMIT} enemies, {BULLETS} bullets, {DASH_CRYSTALS} dash crystals");
+    let brute_hits = measure("bullets brute force", || brute_force(&enemies, &bullets));
+    let grid_hits = measure("bullets spatial grid", || {
+        fill(&mut enemy_grid, &enemies);
+        grid(&enemy_grid, &bullets)
+    });
+    assert_eq!(brute_hits, grid_hits);
+    let brute_hits = measure("crystals brute force", || brute_force(&crystals, &player));
+    let grid_hits = measure("crystals spatial grid", || grid(&crystal_grid, &player));
+    assert_eq!(brute_hits, grid_hits);
+}
@@ -0,0 +1,59 @@
+// entities further than ACTIVE_RADIUS from the player tick at REDUCED_TICK rate,
+// further than REDUCED_RADIUS they sleep
+pub const ACTIVE_RADIUS: f32 = 1024.0;
+pub const REDUCED_RADIUS: f32 = 2048.0;
+pub const REDUCED_TICK: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ActivityLevel {
+    Active,
+    Reduced,
+    Sleeping,
+}
+
+// level of detail by distance to the player, systems advance the entity by `dt`
+// which is 0.0 while sleeping and accumulated frame time on reduced ticks
+pub struct Activity {
+    pub level: ActivityLevel,
+    pub dt: f32,
+    pub accumulated: f32,
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self {
+            level: ActivityLevel::Active,
+            dt: 0.0,
+            accumulated: 0.0,
+        }
+    }
+}
+
+impl Activity {
+    pub fn update(self: &mut Self, player_distance: f32, dt: f32) {
+        if player_distance <= ACTIVE_RADIUS {
+            self.level = ActivityLevel::Active;
+            self.dt = dt + self.accumulated;
+            self.accumulated = 0.0;
+        } else if player_distance <= REDUCED_RADIUS {
+            self.level = ActivityLevel::Reduced;
+            self.accumulated += dt;
+            if self.accumulated >= REDUCED_TICK {
+                self.dt = self.accumulated;
+                self.accumulated = 0.0;
+            } else {
+                self.dt = 0.0;
+            }
+        } else {
+            // sleeping entities don't bank time, they resume as if nothing happened
+            self.level = ActivityLevel::Sleeping;
+            self.dt = 0.0;
+            self.accumulated = 0.0;
+        }
+    }
+}
+
+// frame time for entities with level of detail, full dt for the rest
+pub fn activity_dt(activity: Option<&Activity>, dt: f32) -> f32 {
+    activity.map_or(dt, |activity| activity.dt)
+}
@@ -0,0 +1,373 @@
+// enemy AI run on the world alone, so the activity bench times the same code as the game
+
+use glam::Vec2;
+use rand::{rngs::ThreadRng, Rng};
+
+use crate::{
+    activity::activity_dt,
+    bullet_pattern::Aim,
+    components::{self, Faction},
+    flowfield::FlowField,
+    projectile::Projectiles,
+    render::Tilemap,
+    spatial::{Entry, SpatialGrid},
+    steering,
+};
+
+pub fn system_activity(world: &mut hecs::World, dt: f32) {
+    puffin::profile_scope!("system_activity");
+    let mut optional_player_position = None;
+    for (_id, (transform, _)) in &mut world.query::<(&components::Transform, &components::Player)>()
+    {
+        optional_player_position = Some(transform.position);
+    }
+    let Some(player_position) = optional_player_position else {
+        return;
+    };
+    for (_id, (transform, activity)) in
+        world.query_mut::<(&components::Transform, &mut components::Activity)>()
+    {
+        activity.update(transform.position.distance(player_position), dt);
+    }
+}
+
+// player_shots are where the player fired this frame, enemies hearing them get alerted
+pub fn system_awareness(
+    world: &mut hecs::World,
+    tilemap: &Tilemap,
+    obstacles: &SpatialGrid,
+    player_shots: &[Vec2],
+    rng: &mut ThreadRng,
+    dt: f32,
+) {
+    puffin::profile_scope!("system_awareness");
+    let mut optional_player_position = None;
+    for (_id, (transform, _)) in &mut world.query::<(&components::Transform, &components::Player)>()
+    {
+        optional_player_position = Some(transform.position);
+    }
+    let Some(player_position) = optional_player_position else {
+        return;
+    };
+    // shots are heard and enemies that spot the player shout to the ones around them
+    let mut alert_sources = player_shots.to_vec();
+    for (_id, (transform, awareness, collider, activity)) in world.query_mut::<(
+        &mut components::Transform,
+        &mut components::Awareness,
+        Option<&components::Collider>,
+        Option<&components::Activity>,
+    )>() {
+        let dt = activity_dt(activity, dt);
+        if dt == 0.0 {
+            continue;
+        }
+        let distance = transform.position.distance(player_position);
+        let sees = |radius: f32| {
+            distance <= radius && tilemap.line_of_sight(transform.position, player_position)
+        };
+        awareness.timer -= dt;
+        if awareness.is_unaware() && sees(awareness.detection_radius) {
+            awareness.alert(player_position);
+            continue;
+        }
+        match awareness.state {
+            components::AwarenessState::Idle => {
+                if awareness.timer <= 0.0 {
+                    awareness.state = components::AwarenessState::Wander;
+                    awareness.timer = rng.gen_range(2.0..5.0);
+                    awareness.heading = rng.gen_range(0.0..360.0);
+                }
+            }
+            components::AwarenessState::Wander => {
+                let velocity = steering::wander(&mut awareness.heading, 90.0, rng, dt)
+                    * awareness.wander_speed;
+                move_on_terrain(tilemap, obstacles, transform, collider, velocity * dt);
+                if awareness.timer <= 0.0 {
+                    awareness.state = components::AwarenessState::Idle;
+                    awareness.timer = rng.gen_range(1.0..3.0);
+                }
+            }
+            components::AwarenessState::Alerted => {
+                if awareness.timer <= 0.0 {
+                    awareness.state = components::AwarenessState::Chasing;
+                    awareness.timer = awareness.lose_timeout;
+                    alert_sources.push(transform.position);
+                }
+            }
+            components::AwarenessState::Chasing => {
+                if sees(awareness.lose_radius) {
+                    awareness.timer = awareness.lose_timeout;
+                    awareness.last_known_position = player_position;
+                } else if awareness.timer <= 0.0 {
+                    awareness.state = components::AwarenessState::Lost;
+                    awareness.timer = awareness.lose_timeout;
+                }
+            }
+            components::AwarenessState::Lost => {
+                // search where the player was last seen, then give up
+                let to_last_known = awareness.last_known_position - transform.position;
+                let velocity = to_last_known.normalize_or_zero() * awareness.wander_speed * 2.0;
+                move_on_terrain(tilemap, obstacles, transform, collider, velocity * dt);
+                if awareness.timer <= 0.0 || to_last_known.length() < 32.0 {
+                    awareness.state = components::AwarenessState::Idle;
+                    awareness.timer = rng.gen_range(1.0..3.0);
+                }
+            }
+        }
+    }
+    if alert_sources.is_empty() {
+        return;
+    }
+    for (_id, (transform, awareness)) in
+        world.query_mut::<(&components::Transform, &mut components::Awareness)>()
+    {
+        if awareness.is_unaware()
+            && alert_sources
+                .iter()
+                .any(|source| source.distance(transform.position) <= awareness.alert_radius)
+        {
+            awareness.alert(player_position);
+        }
+    }
+}
+
+pub fn system_ghost_ai(
+    world: &mut hecs::World,
+    neighbours: &SpatialGrid,
+    flow_field: &FlowField,
+    tilemap: &Tilemap,
+    obstacles: &SpatialGrid,
+    dt: f32,
+) {
+    puffin::profile_scope!("system_ghost_ai");
+    let mut optional_player_position = None;
+    for (_id, (transform, _)) in &mut world.query::<(&components::Transform, &components::Player)>()
+    {
+        optional_player_position = Some(transform.position);
+    }
+    if let Some(target_pos) = optional_player_position {
+        // ghost move
+        for (id, (transform, ghost_ai, awareness, collider, activity)) in world.query_mut::<(
+            &mut components::Transform,
+            &mut components::GhostAI,
+            Option<&components::Awareness>,
+            Option<&components::Collider>,
+            Option<&components::Activity>,
+        )>() {
+            let dt = activity_dt(activity, dt);
+            if dt == 0.0 {
+                continue;
+            }
+            let difference = target_pos - transform.position;
+            let chasing = awareness.map_or(difference.length() <= ghost_ai.radius, |awareness| {
+                awareness.is_chasing()
+            });
+            if chasing {
+                let separation = steering::separation(
+                    id,
+                    transform.position,
+                    neighbours,
+                    ghost_ai.separation_radius,
+                );
+                let cohesion = steering::cohesion(
+                    id,
+                    transform.position,
+                    neighbours,
+                    ghost_ai.cohesion_radius,
+                );
+                let direction =
+                    chase_direction(flow_field, tilemap, transform.position, target_pos)
+                        + separation * ghost_ai.separation_weight
+                        + cohesion * ghost_ai.cohesion_weight;
+                ghost_ai.velocity = direction.normalize_or_zero() * ghost_ai.speed;
+                move_on_terrain(
+                    tilemap,
+                    obstacles,
+                    transform,
+                    collider,
+                    dt * ghost_ai.velocity,
+                );
+            }
+        }
+    }
+}
+
+// bullets the enemies fire this frame as (projectile, position, direction, faction)
+pub fn system_shooting_enemies(
+    world: &mut hecs::World,
+    projectiles: &Projectiles,
+    dt: f32,
+) -> Vec<(&'static str, Vec2, Vec2, Faction)> {
+    puffin::profile_scope!("system_shooting_enemies");
+    let mut optional_player = None;
+    let mut bullets_to_create = vec![];
+    for (_id, (transform, controller, _)) in &mut world.query::<(
+        &components::Transform,
+        &components::PlayerController,
+        &components::Player,
+    )>() {
+        optional_player = Some((transform.position, controller.velocity));
+    }
+    for (_id, (transform, shooting, faction, activity)) in world.query_mut::<(
+        &components::Transform,
+        &mut components::ShootingEnemy,
+        &Faction,
+        Option<&components::Activity>,
+    )>() {
+        let dt = activity_dt(activity, dt);
+        if dt == 0.0 {
+            continue;
+        }
+        shooting.timer -= dt;
+        if let Some((target_position, target_velocity)) = optional_player {
+            let aim = Aim {
+                origin: transform.position,
+                target: target_position,
+                target_velocity,
+                bullet_speed: projectiles.speed(shooting.projectile),
+            };
+            let mut directions = shooting
+                .pattern
+                .update(&mut shooting.pattern_state, &aim, dt);
+            let distance = target_position.distance(transform.position);
+            if shooting.timer <= 0.0
+                && distance <= shooting.range
+                && !shooting.pattern.is_busy(&shooting.pattern_state)
+            {
+                shooting.timer = shooting.cooldown;
+                directions.extend(shooting.pattern.start(&mut shooting.pattern_state, &aim));
+            }
+            for direction in directions {
+                bullets_to_create.push((
+                    shooting.projectile,
+                    transform.position,
+                    direction,
+                    *faction,
+                ));
+            }
+        }
+    }
+    bullets_to_create
+}
+
+pub fn system_orbit_ai(
+    world: &mut hecs::World,
+    flow_field: &FlowField,
+    tilemap: &Tilemap,
+    obstacles: &SpatialGrid,
+    dt: f32,
+) {
+    puffin::profile_scope!("system_orbit_ai");
+    let mut optional_player_position = None;
+    for (_id, (transform, _)) in &mut world.query::<(&components::Transform, &components::Player)>()
+    {
+        optional_player_position = Some(transform.position);
+    }
+    if let Some(target_pos) = optional_player_position {
+        for (_id, (transform, orbit_ai, awareness, collider, activity)) in world.query_mut::<(
+            &mut components::Transform,
+            &mut components::OrbitAI,
+            Option<&components::Awareness>,
+            Option<&components::Collider>,
+            Option<&components::Activity>,
+        )>() {
+            let dt = activity_dt(activity, dt);
+            if dt == 0.0 {
+                continue;
+            }
+            let offset = transform.position - target_pos;
+            let r = offset.length();
+            let chasing = awareness.map_or(r <= orbit_ai.radius_ghosting, |awareness| {
+                awareness.is_chasing()
+            });
+            if !chasing {
+                continue;
+            }
+            let desired_velocity = if r > orbit_ai.band_outer {
+                chase_direction(flow_field, tilemap, transform.position, target_pos)
+                    * orbit_ai.speed
+            } else {
+                if orbit_ai.strafe {
+                    orbit_ai.strafe_timer -= dt;
+                    if orbit_ai.strafe_timer <= 0.0 {
+                        orbit_ai.strafe_timer = orbit_ai.strafe_interval;
+                        orbit_ai.clockwise = !orbit_ai.clockwise;
+                    }
+                }
+                steering::orbit(
+                    offset,
+                    orbit_ai.radius_orbiting,
+                    (orbit_ai.band_inner, orbit_ai.band_outer),
+                    orbit_ai.angular_speed,
+                    orbit_ai.clockwise,
+                    orbit_ai.speed,
+                )
+            };
+            // blend so entering and leaving the orbit doesn't snap
+            orbit_ai.velocity = orbit_ai
+                .velocity
+                .lerp(desired_velocity, (orbit_ai.entry_smoothing * dt).min(1.0));
+            move_on_terrain(
+                tilemap,
+                obstacles,
+                transform,
+                collider,
+                dt * orbit_ai.velocity,
+            );
+        }
+    }
+}
+
+pub fn system_animation(world: &mut hecs::World, dt: f32) {
+    puffin::profile_scope!("system_animation");
+    for (_id, (animation_state, activity)) in
+        world.query_mut::<(&mut components::Animation, Option<&components::Activity>)>()
+    {
+        let dt = activity_dt(activity, dt);
+        if dt == 0.0 {
+            continue;
+        }
+        animation_state
+            .state
+            .update(std::time::Duration::from_secs_f32(dt));
+    }
+}
+
+// direction toward target following the flow field, straight line when field has no answer
+pub fn chase_direction(
+    flow_field: &FlowField,
+    tilemap: &Tilemap,
+    position: Vec2,
+    target: Vec2,
+) -> Vec2 {
+    flow_field
+        .direction(tilemap.world_to_tile(position))
+        .unwrap_or_else(|| (target - position).normalize_or_zero())
+}
+
+// moves by delta scaled by the terrain underfoot, colliders slide along unwalkable tiles
+// and solid props, props it already overlaps don't block it so nothing gets stuck inside one
+pub fn move_on_terrain(
+    tilemap: &Tilemap,
+    obstacles: &SpatialGrid,
+    transform: &mut components::Transform,
+    collider: Option<&components::Collider>,
+    delta: Vec2,
+) {
+    let Some(collider) = collider else {
+        transform.position += delta * tilemap.properties_at(transform.position).speed_multiplier;
+        return;
+    };
+    let center = collider.center(transform.position);
+    let delta = delta * tilemap.properties_at(center).speed_multiplier;
+    let (min, max) = collider.bounds(transform.position);
+    let overlapping = |min: Vec2, max: Vec2| {
+        obstacles
+            .query_rect(min, max)
+            .filter(move |entry: &Entry| entry.min.cmplt(max).all() && min.cmplt(entry.max).all())
+    };
+    let inside: Vec<hecs::Entity> = overlapping(min, max).map(|entry| entry.id).collect();
+    transform.position += tilemap.slide(min, max, delta, |min, max| {
+        overlapping(min, max).any(|entry| !inside.contains(&entry.id))
+    });
+}
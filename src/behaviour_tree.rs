@@ -0,0 +1,411 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use glam::Vec2;
+use rand::{rngs::ThreadRng, Rng};
+use serde::Deserialize;
+
+use crate::{
+    bullet_pattern::{Aim, BulletPattern, PatternState},
+    projectile::Projectiles,
+    steering,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+// what an enemy knows this tick, filled by the behaviour tree system
+pub struct Blackboard<'a> {
+    pub position: Vec2,
+    pub player_position: Vec2,
+    pub player_velocity: Vec2,
+    pub player_distance: f32,
+    pub player_visible: bool,
+    pub path_direction: Vec2, // toward player following the flow field
+    pub projectiles: &'a Projectiles,
+    pub projectile: &'a str, // fired by shoot nodes that don't name their own
+    pub dt: f32,
+}
+
+// what leaves decided to do this tick
+#[derive(Default)]
+pub struct Actions {
+    pub velocity: Vec2,
+    pub bullets: Vec<(String, Vec2)>, // projectile and direction
+}
+
+// read from json by "type" in snake case, fields keeping progress are skipped,
+// see res/behaviour/skirmisher.json
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    // composites
+    Sequence {
+        children: Vec<Node>,
+        #[serde(skip)]
+        current: usize,
+    },
+    Selector {
+        children: Vec<Node>,
+        #[serde(skip)]
+        running: Option<usize>,
+    },
+    Parallel {
+        children: Vec<Node>,
+    },
+    // decorators
+    Inverter {
+        child: Box<Node>,
+    },
+    Succeeder {
+        child: Box<Node>,
+    },
+    Repeat {
+        child: Box<Node>,
+    },
+    Cooldown {
+        seconds: f32,
+        #[serde(skip)]
+        timer: f32,
+        child: Box<Node>,
+    },
+    // conditions
+    PlayerWithin {
+        radius: f32,
+    },
+    PlayerVisible,
+    // leaves
+    Chase {
+        speed: f32,
+        distance: f32,
+    },
+    Orbit {
+        radius: f32,
+        angular_speed: f32,
+        clockwise: bool,
+        speed: f32,
+    },
+    Flee {
+        speed: f32,
+        distance: f32,
+    },
+    Wander {
+        speed: f32,
+        jitter: f32,
+        #[serde(skip)]
+        heading: f32,
+    },
+    // running until a multi-frame pattern like a burst is done
+    Shoot {
+        pattern: BulletPattern,
+        #[serde(skip)]
+        state: PatternState,
+        range: f32,
+        #[serde(default)]
+        projectile: Option<String>, // see projectile.rs, the enemy's own when missing
+        #[serde(skip)]
+        firing: bool,
+    },
+    Wait {
+        seconds: f32,
+        #[serde(skip)]
+        elapsed: f32,
+    },
+}
+
+impl Node {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        serde_json::from_str(&text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn tick(
+        self: &mut Self,
+        blackboard: &Blackboard,
+        actions: &mut Actions,
+        rng: &mut ThreadRng,
+    ) -> Status {
+        let dt = blackboard.dt;
+        match self {
+            Node::Sequence { children, current } => {
+                while *current < children.len() {
+                    match children[*current].tick(blackboard, actions, rng) {
+                        Status::Success => *current += 1,
+                        Status::Running => return Status::Running,
+                        Status::Failure => {
+                            *current = 0;
+                            return Status::Failure;
+                        }
+                    }
+                }
+                *current = 0;
+                Status::Success
+            }
+            // re-evaluated from the top every tick so higher priority branches can interrupt
+            Node::Selector { children, running } => {
+                for index in 0..children.len() {
+                    let status = children[index].tick(blackboard, actions, rng);
+                    if status == Status::Failure {
+                        continue;
+                    }
+                    if let Some(previous) = *running {
+                        if previous != index {
+                            children[previous].reset();
+                        }
+                    }
+                    *running = if status == Status::Running {
+                        Some(index)
+                    } else {
+                        None
+                    };
+                    return status;
+                }
+                *running = None;
+                Status::Failure
+            }
+            // first child decides the result, the rest run alongside it (e.g. shoot while orbiting)
+            Node::Parallel { children } => {
+                let mut result = Status::Success;
+                for (index, child) in children.iter_mut().enumerate() {
+                    let status = child.tick(blackboard, actions, rng);
+                    if index == 0 {
+                        result = status;
+                    }
+                }
+                result
+            }
+            Node::Inverter { child } => match child.tick(blackboard, actions, rng) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeeder { child } => {
+                child.tick(blackboard, actions, rng);
+                Status::Success
+            }
+            Node::Repeat { child } => {
+                if child.tick(blackboard, actions, rng) != Status::Running {
+                    child.reset();
+                }
+                Status::Running
+            }
+            Node::Cooldown {
+                seconds,
+                timer,
+                child,
+            } => {
+                *timer -= dt;
+                if *timer > 0.0 {
+                    return Status::Failure;
+                }
+                let status = child.tick(blackboard, actions, rng);
+                if status == Status::Success {
+                    *timer = *seconds;
+                }
+                status
+            }
+            Node::PlayerWithin { radius } => status(blackboard.player_distance <= *radius),
+            Node::PlayerVisible => status(blackboard.player_visible),
+            Node::Chase { speed, distance } => {
+                if blackboard.player_distance <= *distance {
+                    return Status::Success;
+                }
+                actions.velocity = blackboard.path_direction * *speed;
+                Status::Running
+            }
+            Node::Orbit {
+                radius,
+                angular_speed,
+                clockwise,
+                speed,
+            } => {
+                actions.velocity = steering::orbit(
+                    blackboard.position - blackboard.player_position,
+                    *radius,
+                    (*radius * 0.8, *radius * 1.3),
+                    *angular_speed,
+                    *clockwise,
+                    *speed,
+                );
+                Status::Running
+            }
+            Node::Flee { speed, distance } => {
+                if blackboard.player_distance >= *distance {
+                    return Status::Success;
+                }
+                actions.velocity =
+                    (blackboard.position - blackboard.player_position).normalize_or_zero() * *speed;
+                Status::Running
+            }
+            Node::Wander {
+                speed,
+                jitter,
+                heading,
+            } => {
+                actions.velocity = steering::wander(heading, *jitter, rng, dt) * *speed;
+                Status::Running
+            }
+            Node::Shoot {
+                pattern,
+                state,
+                range,
+                projectile,
+                firing,
+            } => {
+                let projectile = projectile.as_deref().unwrap_or(blackboard.projectile);
+                let aim = Aim {
+                    origin: blackboard.position,
+                    target: blackboard.player_position,
+                    target_velocity: blackboard.player_velocity,
+                    bullet_speed: blackboard.projectiles.speed(projectile),
+                };
+                let fire = |directions: Vec<Vec2>| {
+                    directions
+                        .into_iter()
+                        .map(|direction| (projectile.to_string(), direction))
+                };
+                actions
+                    .bullets
+                    .extend(fire(pattern.update(state, &aim, dt)));
+                if *firing {
+                    if pattern.is_busy(state) {
+                        return Status::Running;
+                    }
+                    *firing = false;
+                    return Status::Success;
+                }
+                if pattern.is_busy(state) {
+                    return Status::Running;
+                }
+                if blackboard.player_distance > *range || !blackboard.player_visible {
+                    return Status::Failure;
+                }
+                actions.bullets.extend(fire(pattern.start(state, &aim)));
+                if pattern.is_busy(state) {
+                    *firing = true;
+                    return Status::Running;
+                }
+                Status::Success
+            }
+            Node::Wait { seconds, elapsed } => {
+                *elapsed += dt;
+                if *elapsed < *seconds {
+                    return Status::Running;
+                }
+                *elapsed = 0.0;
+                Status::Success
+            }
+        }
+    }
+
+    // forget progress of running nodes, used when a selector switches branch
+    pub fn reset(self: &mut Self) {
+        match self {
+            Node::Sequence { children, current } => {
+                *current = 0;
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::Selector { children, running } => {
+                *running = None;
+                children.iter_mut().for_each(Node::reset);
+            }
+            Node::Parallel { children } => children.iter_mut().for_each(Node::reset),
+            Node::Inverter { child } | Node::Succeeder { child } | Node::Repeat { child } => {
+                child.reset()
+            }
+            Node::Cooldown { child, .. } => child.reset(),
+            Node::Shoot { firing, .. } => *firing = false,
+            Node::Wait { elapsed, .. } => *elapsed = 0.0,
+            _ => {}
+        }
+    }
+
+    // flips the direction of every orbit
+    pub fn mirror(self: &mut Self) {
+        match self {
+            Node::Sequence { children, .. }
+            | Node::Selector { children, .. }
+            | Node::Parallel { children } => children.iter_mut().for_each(Node::mirror),
+            Node::Inverter { child }
+            | Node::Succeeder { child }
+            | Node::Repeat { child }
+            | Node::Cooldown { child, .. } => child.mirror(),
+            Node::Orbit { clockwise, .. } => *clockwise = !*clockwise,
+            _ => {}
+        }
+    }
+}
+
+fn status(condition: bool) -> Status {
+    if condition {
+        Status::Success
+    } else {
+        Status::Failure
+    }
+}
+
+// component, every enemy owns its own tree so nodes can keep their progress inline
+pub struct BehaviourTree {
+    pub root: Node,
+}
+
+impl BehaviourTree {
+    pub fn new(root: Node) -> Self {
+        Self { root }
+    }
+    pub fn tick(self: &mut Self, blackboard: &Blackboard, rng: &mut ThreadRng) -> Actions {
+        let mut actions = Actions::default();
+        self.root.tick(blackboard, &mut actions, rng);
+        actions
+    }
+}
+
+// trees of the enemies driven by one, every res/behaviour/<enemy name>.json,
+// enemies without a file keep their own components
+pub struct Behaviours {
+    trees: HashMap<String, Node>,
+}
+
+impl Behaviours {
+    // broken files are reported and skipped
+    pub fn load(directory: &str) -> Self {
+        let mut trees = HashMap::new();
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                println!("Couldn't read behaviour trees {}: {}", directory, error);
+                return Self { trees };
+            }
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path
+                .extension()
+                .map_or(true, |extension| extension != "json")
+            {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            match Node::load(&path) {
+                Ok(root) => {
+                    trees.insert(name.to_string(), root);
+                }
+                Err(error) => println!("Couldn't load behaviour tree {}", error),
+            }
+        }
+        Self { trees }
+    }
+    // a fresh copy for one enemy, half of them orbit the other way round
+    pub fn instantiate(self: &Self, name: &str, rng: &mut ThreadRng) -> Option<BehaviourTree> {
+        let mut root = self.trees.get(name)?.clone();
+        if rng.gen_bool(0.5) {
+            root.mirror();
+        }
+        Some(BehaviourTree::new(root))
+    }
+}
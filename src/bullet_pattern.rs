@@ -0,0 +1,143 @@
+use glam::Vec2;
+use serde::Deserialize;
+
+// Describes how a shooting enemy fires, interpreted by `start`/`update`
+// so new enemies are just a different pattern value, read from json by "type"
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BulletPattern {
+    Aimed,
+    Spread {
+        count: u32,
+        angle: f32,
+    }, // angle is the whole fan in degrees
+    Ring {
+        count: u32,
+    },
+    Spiral {
+        arms: u32,
+        step: f32,
+    }, // step in degrees the arms rotate every volley
+    Burst {
+        shots: u32,
+        delay: f32,
+        pattern: Box<BulletPattern>,
+    },
+    Leading, // aims where the target will be when the bullet arrives
+}
+
+#[derive(Clone, Default)]
+pub struct PatternState {
+    spiral_angle: f32,
+    burst_remaining: u32,
+    burst_timer: f32,
+}
+
+pub struct Aim {
+    pub origin: Vec2,
+    pub target: Vec2,
+    pub target_velocity: Vec2,
+    pub bullet_speed: f32,
+}
+
+impl BulletPattern {
+    // fire a volley, returns directions of bullets to create
+    pub fn start(self: &Self, state: &mut PatternState, aim: &Aim) -> Vec<Vec2> {
+        match self {
+            BulletPattern::Burst {
+                shots,
+                delay,
+                pattern,
+            } => {
+                state.burst_remaining = shots.saturating_sub(1);
+                state.burst_timer = *delay;
+                pattern.start(state, aim)
+            }
+            _ => self.volley(state, aim),
+        }
+    }
+
+    // keeps patterns that span multiple frames going, call every frame
+    pub fn update(self: &Self, state: &mut PatternState, aim: &Aim, dt: f32) -> Vec<Vec2> {
+        let mut directions = vec![];
+        if let BulletPattern::Burst { delay, pattern, .. } = self {
+            state.burst_timer -= dt;
+            while state.burst_remaining > 0 && state.burst_timer <= 0.0 {
+                state.burst_remaining -= 1;
+                state.burst_timer += delay;
+                directions.extend(pattern.start(state, aim));
+            }
+        }
+        directions
+    }
+
+    pub fn is_busy(self: &Self, state: &PatternState) -> bool {
+        state.burst_remaining > 0
+    }
+
+    fn volley(self: &Self, state: &mut PatternState, aim: &Aim) -> Vec<Vec2> {
+        let aimed = (aim.target - aim.origin).normalize_or_zero();
+        match self {
+            BulletPattern::Aimed => vec![aimed],
+            BulletPattern::Spread { count, angle } => {
+                if *count <= 1 {
+                    return vec![aimed];
+                }
+                let step = angle / (*count - 1) as f32;
+                (0..*count)
+                    .map(|i| rotate(aimed, -angle / 2.0 + step * i as f32))
+                    .collect()
+            }
+            BulletPattern::Ring { count } => fan(aimed, *count),
+            BulletPattern::Spiral { arms, step } => {
+                state.spiral_angle = (state.spiral_angle + step) % 360.0;
+                fan(rotate(Vec2::X, state.spiral_angle), *arms)
+            }
+            BulletPattern::Burst { pattern, .. } => pattern.volley(state, aim),
+            BulletPattern::Leading => vec![leading_direction(aim).unwrap_or(aimed)],
+        }
+    }
+}
+
+// count directions evenly spaced around full circle starting at first
+fn fan(first: Vec2, count: u32) -> Vec<Vec2> {
+    let step = 360.0 / count.max(1) as f32;
+    (0..count).map(|i| rotate(first, step * i as f32)).collect()
+}
+
+fn rotate(direction: Vec2, degrees: f32) -> Vec2 {
+    Vec2::from_angle(degrees.to_radians()).rotate(direction)
+}
+
+// solves |target + velocity * t - origin| = speed * t for smallest positive t
+fn leading_direction(aim: &Aim) -> Option<Vec2> {
+    let to_target = aim.target - aim.origin;
+    let a = aim.target_velocity.length_squared() - aim.bullet_speed * aim.bullet_speed;
+    let b = 2.0 * to_target.dot(aim.target_velocity);
+    let c = to_target.length_squared();
+    let time = if a.abs() < f32::EPSILON {
+        if b.abs() < f32::EPSILON {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let t1 = (-b - root) / (2.0 * a);
+        let t2 = (-b + root) / (2.0 * a);
+        match (t1 > 0.0, t2 > 0.0) {
+            (true, true) => t1.min(t2),
+            (true, false) => t1,
+            (false, true) => t2,
+            (false, false) => return None,
+        }
+    };
+    if time <= 0.0 {
+        return None;
+    }
+    let predicted = aim.target + aim.target_velocity * time;
+    Some((predicted - aim.origin).normalize_or_zero())
+}
@@ -0,0 +1,209 @@
+use glam::Vec2;
+
+use crate::{
+    components::{Collider, Swept, Transform},
+    spatial::{Entry, SpatialGrid},
+};
+
+// collision layers, a collider reports contacts with colliders whose layer is in its mask
+pub const PLAYER: u32 = 1 << 0;
+pub const ENEMY: u32 = 1 << 1;
+pub const PLAYER_BULLET: u32 = 1 << 2;
+pub const ENEMY_BULLET: u32 = 1 << 3;
+pub const PICKUP: u32 = 1 << 4;
+pub const TERRAIN: u32 = 1 << 5;
+
+#[derive(Clone, Copy)]
+pub enum Shape {
+    Aabb { size: Vec2 },
+    Circle { radius: f32 },
+}
+
+// `a` has `b`'s layer in its mask, pairs masking each other are reported both ways,
+// `time` is when along a swept `a`'s movement this frame they first touched (0..=1)
+#[derive(Clone, Copy)]
+pub struct Contact {
+    pub a: hecs::Entity,
+    pub b: hecs::Entity,
+    pub time: f32,
+}
+
+impl Collider {
+    pub fn center(self: &Self, position: Vec2) -> Vec2 {
+        position + self.offset
+    }
+    pub fn bounds(self: &Self, position: Vec2) -> (Vec2, Vec2) {
+        let half = self.half_extents();
+        let center = self.center(position);
+        (center - half, center + half)
+    }
+    fn half_extents(self: &Self) -> Vec2 {
+        match self.shape {
+            Shape::Aabb { size } => size / 2.0,
+            Shape::Circle { radius } => Vec2::splat(radius),
+        }
+    }
+    // earliest time (0..=1) moving from `from` to `to` touches the other collider,
+    // the other one is treated as standing still, anything but two circles is
+    // swept as boxes so circles clip box corners slightly early
+    pub fn sweep(
+        self: &Self,
+        from: Vec2,
+        to: Vec2,
+        other: &Collider,
+        other_position: Vec2,
+    ) -> Option<f32> {
+        let start = self.center(from);
+        let delta = to - from;
+        let other_center = other.center(other_position);
+        match (self.shape, other.shape) {
+            (
+                Shape::Circle { radius },
+                Shape::Circle {
+                    radius: other_radius,
+                },
+            ) => segment_circle(start, delta, other_center, radius + other_radius),
+            _ => {
+                let half = self.half_extents() + other.half_extents();
+                segment_box(start, delta, other_center - half, other_center + half)
+            }
+        }
+    }
+    pub fn overlaps(self: &Self, position: Vec2, other: &Collider, other_position: Vec2) -> bool {
+        let center = self.center(position);
+        let other_center = other.center(other_position);
+        match (self.shape, other.shape) {
+            (
+                Shape::Circle { radius },
+                Shape::Circle {
+                    radius: other_radius,
+                },
+            ) => center.distance_squared(other_center) <= (radius + other_radius).powi(2),
+            (Shape::Circle { radius }, Shape::Aabb { .. }) => {
+                let (min, max) = other.bounds(other_position);
+                center.clamp(min, max).distance_squared(center) <= radius * radius
+            }
+            (Shape::Aabb { .. }, Shape::Circle { .. }) => {
+                other.overlaps(other_position, self, position)
+            }
+            (Shape::Aabb { .. }, Shape::Aabb { .. }) => {
+                let (min, max) = self.bounds(position);
+                let (other_min, other_max) = other.bounds(other_position);
+                min.cmple(other_max).all() && other_min.cmple(max).all()
+            }
+        }
+    }
+}
+
+fn segment_circle(start: Vec2, delta: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = start - center;
+    let c = offset.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let a = delta.length_squared();
+    let b = offset.dot(delta);
+    let discriminant = b * b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / a;
+    (0.0..=1.0).contains(&time).then_some(time)
+}
+
+// slab test of the segment against the box
+fn segment_box(start: Vec2, delta: Vec2, min: Vec2, max: Vec2) -> Option<f32> {
+    let mut enter = 0.0_f32;
+    let mut exit = 1.0_f32;
+    for axis in 0..2 {
+        if delta[axis] == 0.0 {
+            if start[axis] < min[axis] || start[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - start[axis]) / delta[axis];
+        let t2 = (max[axis] - start[axis]) / delta[axis];
+        enter = enter.max(t1.min(t2));
+        exit = exit.min(t1.max(t2));
+        if enter > exit {
+            return None;
+        }
+    }
+    Some(enter)
+}
+
+pub fn insert(grid: &mut SpatialGrid, id: hecs::Entity, collider: &Collider, position: Vec2) {
+    let (min, max) = collider.bounds(position);
+    grid.insert_rect(id, min, max - min);
+}
+
+pub fn remove(grid: &mut SpatialGrid, id: hecs::Entity, collider: &Collider, position: Vec2) {
+    let (min, max) = collider.bounds(position);
+    grid.remove(&Entry { id, min, max });
+}
+
+// narrow phase for every dynamic collider against moving colliders in `dynamic_grid`
+// and static ones kept in `static_grid`, swept colliders test their whole movement
+// this frame, contacts come out ordered by time
+pub fn find_contacts(
+    world: &hecs::World,
+    dynamic_grid: &SpatialGrid,
+    static_grid: &SpatialGrid,
+    contacts: &mut Vec<Contact>,
+) {
+    for (id, (transform, collider, swept)) in
+        &mut world.query::<(&Transform, &Collider, Option<&Swept>)>()
+    {
+        if collider.is_static || collider.mask == 0 {
+            continue;
+        }
+        let from = swept.map_or(transform.position, |swept| swept.previous_position);
+        let (min, max) = collider.bounds(transform.position);
+        let (start_min, start_max) = collider.bounds(from);
+        let (min, max) = (min.min(start_min), max.max(start_max));
+        for entry in dynamic_grid
+            .query_rect(min, max)
+            .chain(static_grid.query_rect(min, max))
+        {
+            if entry.id == id {
+                continue;
+            }
+            let Ok(other) = world.entity(entry.id) else {
+                continue;
+            };
+            let (Some(other_transform), Some(other_collider)) =
+                (other.get::<&Transform>(), other.get::<&Collider>())
+            else {
+                continue;
+            };
+            if collider.mask & other_collider.layer == 0 {
+                continue;
+            }
+            let time = if swept.is_some() {
+                collider.sweep(
+                    from,
+                    transform.position,
+                    &other_collider,
+                    other_transform.position,
+                )
+            } else {
+                collider
+                    .overlaps(
+                        transform.position,
+                        &other_collider,
+                        other_transform.position,
+                    )
+                    .then_some(0.0)
+            };
+            if let Some(time) = time {
+                contacts.push(Contact {
+                    a: id,
+                    b: entry.id,
+                    time,
+                });
+            }
+        }
+    }
+    contacts.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
@@ -1,7 +1,15 @@
 use glam::{UVec2, Vec2};
 use sdl2_animation::AnimationState;
 
-use crate::player_state;
+pub use crate::activity::Activity;
+use crate::{
+    bullet_pattern::{BulletPattern, PatternState},
+    collision::{self, Shape},
+    director::EnemyKind,
+    player_state,
+    projectile::BulletBehaviour,
+    prop::Prop,
+};
 
 #[derive(Clone)]
 pub struct Transform {
@@ -47,14 +55,88 @@ pub struct Sprite {
     pub size: UVec2,
 }
 
-#[derive(PartialEq)]
-pub enum BulletType {
-    FromPlayer,
-    FromEnemy,
+// hitbox independent of the sprite, shape is centered on position + offset
+pub struct Collider {
+    pub shape: Shape,
+    pub offset: Vec2,
+    pub layer: u32,
+    pub mask: u32,
+    pub is_static: bool, // never moves, kept in the static grid instead of rebuilt
 }
+
+impl Collider {
+    pub fn player() -> Self {
+        Self {
+            shape: Shape::Aabb {
+                size: Vec2::new(40.0, 60.0),
+            },
+            offset: Vec2::new(40.0, 44.0),
+            layer: collision::PLAYER,
+            mask: collision::ENEMY | collision::ENEMY_BULLET | collision::PICKUP,
+            is_static: false,
+        }
+    }
+    pub fn enemy() -> Self {
+        Self {
+            shape: Shape::Circle { radius: 24.0 },
+            offset: Vec2::new(32.0, 36.0),
+            layer: collision::ENEMY,
+            mask: collision::PLAYER | collision::PLAYER_BULLET,
+            is_static: false,
+        }
+    }
+    pub fn pickup() -> Self {
+        Self {
+            shape: Shape::Aabb {
+                size: Vec2::new(48.0, 64.0),
+            },
+            offset: Vec2::new(40.0, 40.0),
+            layer: collision::PICKUP,
+            mask: collision::PLAYER,
+            is_static: true,
+        }
+    }
+}
+
+// fast movers collide along their whole path since the previous frame
+// instead of only where they ended up
+pub struct Swept {
+    pub previous_position: Vec2,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Faction {
+    Player,
+    Snakes,
+    Wild, // attacks everything that isn't wild
+}
+
+impl Faction {
+    pub fn is_hostile(self: &Self, other: &Faction) -> bool {
+        self != other
+    }
+    // layer projectiles fired by this faction collide as
+    pub fn projectile_layer(self: &Self) -> u32 {
+        match self {
+            Faction::Player => collision::PLAYER_BULLET,
+            _ => collision::ENEMY_BULLET,
+        }
+    }
+}
+
+// the faction it hurts is on the projectile entity's Faction
 pub struct Bullet {
     pub velocity: Vec2,
-    pub bullet_type: BulletType,
+    pub damage: u8,
+    pub behaviour: BulletBehaviour,
+    pub range: f32, // despawns after travelling this far
+    pub travelled: f32,
+    pub hits: Vec<hecs::Entity>, // pierced through already, never hit twice
+}
+
+// despawned when it runs out
+pub struct Lifetime {
+    pub remaining: f32,
 }
 
 pub struct Player {
@@ -79,12 +161,20 @@ impl Default for Player {
 
 pub struct CameraTarget;
 
-pub struct Enemy;
+pub struct Enemy {
+    pub kind: EnemyKind,
+}
+// spawned by the director in a wave rather than populating the map, counts against its cap
+pub struct WaveSpawned;
 
 pub struct GhostAI {
     pub velocity: Vec2,
     pub speed: f32,
     pub radius: f32,
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    pub cohesion_radius: f32,
+    pub cohesion_weight: f32,
 }
 
 pub struct OrbitAI {
@@ -93,15 +183,89 @@ pub struct OrbitAI {
     pub angular_speed: f32,
     pub radius_ghosting: f32,
     pub radius_orbiting: f32,
-    pub angle: f32,
-    pub is_orbiting: bool,
-    pub target_pos: Vec2,
+    pub band_inner: f32, // closer than this backs off at full speed
+    pub band_outer: f32, // further than this chases the player
+    pub clockwise: bool,
+    pub entry_smoothing: f32, // how fast velocity blends into the orbit, per sec
+    pub strafe: bool,         // flips orbit direction every strafe_interval
+    pub strafe_interval: f32,
+    pub strafe_timer: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AwarenessState {
+    Idle,
+    Wander,
+    Alerted,
+    Chasing,
+    Lost,
+}
+
+// what an enemy knows about the player, movement AI only chases while Chasing
+pub struct Awareness {
+    pub state: AwarenessState,
+    pub timer: f32, // time left in current state
+    pub heading: f32,
+    pub wander_speed: f32,
+    pub detection_radius: f32,
+    pub lose_radius: f32,
+    pub lose_timeout: f32,
+    pub alert_radius: f32, // hears shots and other enemies spotting the player
+    pub reaction_time: f32,
+    pub last_known_position: Vec2,
+}
+
+impl Awareness {
+    // the player is never lost closer than they can be spotted, otherwise an enemy
+    // would lose them and spot them again right away
+    pub fn with_detection_radius(detection_radius: f32) -> Self {
+        let awareness = Self::default();
+        Self {
+            detection_radius,
+            lose_radius: awareness.lose_radius.max(detection_radius),
+            ..awareness
+        }
+    }
+    pub fn alert(self: &mut Self, player_position: Vec2) {
+        self.state = AwarenessState::Alerted;
+        self.timer = self.reaction_time;
+        self.last_known_position = player_position;
+    }
+    pub fn is_unaware(self: &Self) -> bool {
+        matches!(
+            self.state,
+            AwarenessState::Idle | AwarenessState::Wander | AwarenessState::Lost
+        )
+    }
+    pub fn is_chasing(self: &Self) -> bool {
+        self.state == AwarenessState::Chasing
+    }
+}
+
+impl Default for Awareness {
+    fn default() -> Self {
+        Self {
+            state: AwarenessState::Idle,
+            timer: 0.0,
+            heading: 0.0,
+            wander_speed: 64.0,
+            detection_radius: 512.0 + 128.0,
+            lose_radius: 1024.0,
+            lose_timeout: 3.0,
+            alert_radius: 512.0,
+            reaction_time: 0.3,
+            last_known_position: Vec2::ZERO,
+        }
+    }
 }
 
 pub struct ShootingEnemy {
     pub timer: f32,
     pub cooldown: f32,
     pub range: f32,
+    pub pattern: BulletPattern,
+    pub pattern_state: PatternState,
+    pub projectile: &'static str,
 }
 
 impl Default for ShootingEnemy {
@@ -110,6 +274,9 @@ impl Default for ShootingEnemy {
             timer: 1.0,
             cooldown: 1.0,
             range: 400.0,
+            pattern: BulletPattern::Aimed,
+            pattern_state: PatternState::default(),
+            projectile: "bullet",
         }
     }
 }
@@ -121,10 +288,14 @@ impl Default for OrbitAI {
             speed: 400.0,
             angular_speed: 90.0, // deg per sec
             radius_ghosting: 512.0 + 128.0,
-            radius_orbiting: 256.0,
-            angle: 0.0,
-            is_orbiting: false,
-            target_pos: Vec2::ZERO,
+            radius_orbiting: 300.0,
+            band_inner: 250.0,
+            band_outer: 400.0,
+            clockwise: true,
+            entry_smoothing: 4.0,
+            strafe: false,
+            strafe_interval: 1.5,
+            strafe_timer: 0.0,
         }
     }
 }
@@ -135,6 +306,10 @@ impl Default for GhostAI {
             velocity: Vec2::ZERO,
             speed: 256.0,
             radius: 512.0 + 128.0,
+            separation_radius: 96.0,
+            separation_weight: 1.5,
+            cohesion_radius: 256.0,
+            cohesion_weight: 0.2,
         }
     }
 }
@@ -162,3 +337,20 @@ impl Default for PlayerController {
 
 pub struct DashingCrystal;
 pub struct PointCrystal;
+// point crystals seen on screen, shown on the minimap from then on
+pub struct Discovered;
+
+// collected dash crystal waiting to grow back
+pub struct Regrowth {
+    pub timer: f32,
+}
+
+// summer overheating, a life is lost at 1.0
+#[derive(Default)]
+pub struct Heat {
+    pub level: f32,
+}
+
+pub struct Scenery {
+    pub prop: Prop,
+}
@@ -0,0 +1,368 @@
+use std::f32::consts::TAU;
+
+use glam::Vec2;
+use rand::{rngs::ThreadRng, seq::SliceRandom, Rng};
+
+use crate::render::Tilemap;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EnemyKind {
+    Ghost,
+    // orbiting shooters, differ only in bullet pattern
+    Orbit,
+    Sniper,
+    Spinner,
+    Bloomer,
+    // behaviour tree driven, see res/behaviour/<name>.json
+    Spreader,   // circles the player firing bursts of spreads
+    Skirmisher, // keeps its distance, circles and fires spreads in mid range
+    Lurker,     // wanders until the player gets close, then keeps lunging and popping rings
+}
+
+impl EnemyKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ghost" => Some(Self::Ghost),
+            "orbit" => Some(Self::Orbit),
+            "sniper" => Some(Self::Sniper),
+            "spreader" => Some(Self::Spreader),
+            "spinner" => Some(Self::Spinner),
+            "bloomer" => Some(Self::Bloomer),
+            "skirmisher" => Some(Self::Skirmisher),
+            "lurker" => Some(Self::Lurker),
+            _ => None,
+        }
+    }
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Ghost => "ghost",
+            Self::Orbit => "orbit",
+            Self::Sniper => "sniper",
+            Self::Spreader => "spreader",
+            Self::Spinner => "spinner",
+            Self::Bloomer => "bloomer",
+            Self::Skirmisher => "skirmisher",
+            Self::Lurker => "lurker",
+        }
+    }
+}
+
+const SHOOTERS: [EnemyKind; 7] = [
+    EnemyKind::Orbit,
+    EnemyKind::Sniper,
+    EnemyKind::Spreader,
+    EnemyKind::Spinner,
+    EnemyKind::Bloomer,
+    EnemyKind::Skirmisher,
+    EnemyKind::Lurker,
+];
+
+// hand placed spawn location, None lets the director pick the kind
+#[derive(Clone, Copy)]
+pub struct SpawnPoint {
+    pub position: Vec2,
+    pub kind: Option<EnemyKind>,
+}
+
+pub struct SpawnRequest {
+    pub position: Vec2,
+    pub kind: EnemyKind,
+    pub detection_radius: Option<f32>, // None keeps the component default
+}
+
+// how waves scale with their index, every value is "first wave" + "growth per wave" capped by max
+pub struct DifficultyCurve {
+    pub wave_duration: f32,
+    pub break_duration: f32,
+    pub spawn_rate: f32, // enemies per second
+    pub spawn_rate_growth: f32,
+    pub max_spawn_rate: f32,
+    pub cap: u32, // wave spawned enemies alive at once, the populated ones don't count
+    pub cap_growth: u32,
+    pub max_cap: u32,
+    pub shooters: &'static [EnemyKind], // enemy mix, shooters picked from evenly
+    pub orbit_chance: f32,              // chance of any shooter, rest are ghosts
+    pub orbit_chance_growth: f32,
+    pub max_orbit_chance: f32,
+    pub intensity_per_kill: f32,
+    pub intensity_per_hit: f32,
+    pub intensity_recovery: f32, // per second, pulls intensity back to 1.0
+    pub min_intensity: f32,
+    pub max_intensity: f32,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self {
+            wave_duration: 30.0,
+            break_duration: 5.0,
+            spawn_rate: 1.0,
+            spawn_rate_growth: 0.5,
+            max_spawn_rate: 6.0,
+            cap: 80,
+            cap_growth: 40,
+            max_cap: 320,
+            shooters: &SHOOTERS,
+            orbit_chance: 0.33,
+            orbit_chance_growth: 0.05,
+            max_orbit_chance: 0.6,
+            intensity_per_kill: 0.02,
+            intensity_per_hit: 0.3,
+            intensity_recovery: 0.02,
+            min_intensity: 0.5,
+            max_intensity: 2.0,
+        }
+    }
+}
+
+impl DifficultyCurve {
+    pub fn spawn_rate(self: &Self, wave: u32) -> f32 {
+        (self.spawn_rate + self.spawn_rate_growth * wave as f32).min(self.max_spawn_rate)
+    }
+    pub fn cap(self: &Self, wave: u32) -> u32 {
+        (self.cap + self.cap_growth * wave).min(self.max_cap)
+    }
+    pub fn orbit_chance(self: &Self, wave: u32) -> f32 {
+        (self.orbit_chance + self.orbit_chance_growth * wave as f32).min(self.max_orbit_chance)
+    }
+}
+
+// random positions tried per enemy before spawning fewer
+const SPAWN_ATTEMPTS: u32 = 16;
+// enemies sharing a spawn point get scattered this far around it
+const SPAWN_POINT_SPREAD: f32 = 64.0;
+
+pub struct Director {
+    pub curve: DifficultyCurve,
+    pub spawn_ring_inner: f32, // off screen
+    pub spawn_ring_outer: f32,
+    pub safe_zone_center: Vec2,
+    pub safe_zone_radius: f32,
+    // when not empty enemies only come from these instead of random positions
+    pub spawn_points: Vec<SpawnPoint>,
+    wave: u32,
+    wave_timer: f32,
+    resting: bool,
+    spawn_timer: f32,
+    intensity: f32,
+    last_mob_count: u32,
+    last_lives: Option<u8>,
+}
+
+impl Director {
+    pub fn new(curve: DifficultyCurve) -> Self {
+        Self {
+            wave_timer: curve.wave_duration,
+            curve,
+            spawn_ring_inner: 900.0,
+            spawn_ring_outer: 1400.0,
+            safe_zone_center: Vec2::ZERO,
+            safe_zone_radius: 1024.0,
+            spawn_points: vec![],
+            wave: 0,
+            resting: false,
+            spawn_timer: 0.0,
+            intensity: 1.0,
+            last_mob_count: 0,
+            last_lives: None,
+        }
+    }
+
+    // enemies scattered over the whole map before the first wave
+    pub fn populate(
+        self: &mut Self,
+        count: u32,
+        tilemap: &Tilemap,
+        rng: &mut ThreadRng,
+    ) -> Vec<SpawnRequest> {
+        let mut spawns = vec![];
+        if !self.spawn_points.is_empty() {
+            let points: Vec<SpawnPoint> = self
+                .spawn_points
+                .iter()
+                .filter(|point| self.is_valid_spawn(point.position, tilemap))
+                .copied()
+                .collect();
+            if points.is_empty() {
+                return spawns;
+            }
+            for _ in 0..count {
+                let point = points.choose(rng).unwrap();
+                if let Some(position) = self.scatter(point.position, tilemap, rng) {
+                    spawns.push(SpawnRequest {
+                        position,
+                        kind: point.kind.unwrap_or_else(|| self.pick_kind(rng)),
+                        detection_radius: None,
+                    });
+                }
+            }
+            self.last_mob_count += spawns.len() as u32;
+            return spawns;
+        }
+        // maps smaller than the safe zone or without walkable ground get fewer enemies
+        let (min, max) = tilemap.bounds();
+        for _ in 0..count * SPAWN_ATTEMPTS {
+            if spawns.len() >= count as usize {
+                break;
+            }
+            let position = Vec2::new(rng.gen_range(min.x..max.x), rng.gen_range(min.y..max.y));
+            if self.is_valid_spawn(position, tilemap) {
+                spawns.push(SpawnRequest {
+                    position,
+                    kind: self.pick_kind(rng),
+                    detection_radius: None,
+                });
+            }
+        }
+        self.last_mob_count += spawns.len() as u32;
+        spawns
+    }
+
+    pub fn update(
+        self: &mut Self,
+        dt: f32,
+        player_position: Vec2,
+        mob_count: u32,
+        wave_count: u32, // alive enemies this director spawned in waves
+        tilemap: &Tilemap,
+        rng: &mut ThreadRng,
+    ) -> Vec<SpawnRequest> {
+        self.wave_timer -= dt;
+        if self.wave_timer <= 0.0 {
+            if self.resting {
+                self.resting = false;
+                self.wave += 1;
+                self.wave_timer = self.curve.wave_duration;
+            } else {
+                self.resting = true;
+                self.wave_timer = self.curve.break_duration;
+            }
+        }
+        let mut spawns = vec![];
+        let mut count = mob_count;
+        let mut wave_count = wave_count;
+        if !self.resting {
+            let cap = ((self.curve.cap(self.wave) as f32 * self.intensity) as u32)
+                .min(self.curve.max_cap);
+            self.spawn_timer -= dt * self.curve.spawn_rate(self.wave) * self.intensity;
+            while self.spawn_timer <= 0.0 && wave_count < cap && count < self.curve.max_cap {
+                self.spawn_timer += 1.0;
+                if let Some((position, kind)) = self.ring_position(player_position, tilemap, rng) {
+                    spawns.push(SpawnRequest {
+                        position,
+                        kind: kind.unwrap_or_else(|| self.pick_kind(rng)),
+                        // spawned out of sight, they already know where the player is
+                        detection_radius: Some(self.spawn_ring_outer + 128.0),
+                    });
+                    count += 1;
+                    wave_count += 1;
+                }
+            }
+            // don't bank spawns while at the cap
+            self.spawn_timer = self.spawn_timer.max(0.0);
+        }
+        self.last_mob_count = count;
+        spawns
+    }
+
+    // kills raise the intensity and hits lower it, call before update every frame
+    pub fn track_performance(self: &mut Self, dt: f32, player_lives: u8, mob_count: u32) {
+        let kills = self.last_mob_count.saturating_sub(mob_count);
+        self.intensity += kills as f32 * self.curve.intensity_per_kill;
+        if let Some(last_lives) = self.last_lives {
+            if player_lives < last_lives {
+                self.intensity -= self.curve.intensity_per_hit;
+            }
+        }
+        self.last_lives = Some(player_lives);
+        self.intensity += (1.0 - self.intensity) * (self.curve.intensity_recovery * dt).min(1.0);
+        self.intensity = self
+            .intensity
+            .clamp(self.curve.min_intensity, self.curve.max_intensity);
+    }
+
+    fn ring_position(
+        self: &Self,
+        player_position: Vec2,
+        tilemap: &Tilemap,
+        rng: &mut ThreadRng,
+    ) -> Option<(Vec2, Option<EnemyKind>)> {
+        if !self.spawn_points.is_empty() {
+            return self.spawn_point_position(player_position, tilemap, rng);
+        }
+        for _ in 0..SPAWN_ATTEMPTS {
+            let angle = rng.gen_range(0.0..TAU);
+            let distance = rng.gen_range(self.spawn_ring_inner..self.spawn_ring_outer);
+            let position = player_position + Vec2::new(angle.cos(), angle.sin()) * distance;
+            if self.is_valid_spawn(position, tilemap) {
+                return Some((position, None));
+            }
+        }
+        None
+    }
+
+    // spawn points inside the ring, or further out if the player is far from all of them
+    fn spawn_point_position(
+        self: &Self,
+        player_position: Vec2,
+        tilemap: &Tilemap,
+        rng: &mut ThreadRng,
+    ) -> Option<(Vec2, Option<EnemyKind>)> {
+        let off_screen = |point: &&SpawnPoint| {
+            point.position.distance(player_position) >= self.spawn_ring_inner
+                && self.is_valid_spawn(point.position, tilemap)
+        };
+        let in_ring: Vec<&SpawnPoint> = self
+            .spawn_points
+            .iter()
+            .filter(off_screen)
+            .filter(|point| point.position.distance(player_position) <= self.spawn_ring_outer)
+            .collect();
+        let point = match in_ring.choose(rng) {
+            Some(point) => *point,
+            None => self.spawn_points.iter().filter(off_screen).min_by(|a, b| {
+                a.position
+                    .distance_squared(player_position)
+                    .total_cmp(&b.position.distance_squared(player_position))
+            })?,
+        };
+        Some((self.scatter(point.position, tilemap, rng)?, point.kind))
+    }
+
+    // None when no scattered position around it is a valid spawn
+    fn scatter(
+        self: &Self,
+        position: Vec2,
+        tilemap: &Tilemap,
+        rng: &mut ThreadRng,
+    ) -> Option<Vec2> {
+        let (min, max) = tilemap.bounds();
+        (0..SPAWN_ATTEMPTS)
+            .map(|_| {
+                let offset = Vec2::new(
+                    rng.gen_range(-SPAWN_POINT_SPREAD..SPAWN_POINT_SPREAD),
+                    rng.gen_range(-SPAWN_POINT_SPREAD..SPAWN_POINT_SPREAD),
+                );
+                (position + offset).clamp(min, max)
+            })
+            .find(|position| self.is_valid_spawn(*position, tilemap))
+    }
+
+    // inside the map on walkable ground and out of the safe zone, enemies spawned in
+    // a cliff could never move but would still count towards the cap
+    fn is_valid_spawn(self: &Self, position: Vec2, tilemap: &Tilemap) -> bool {
+        let (min, max) = tilemap.bounds();
+        position.cmpgt(min).all()
+            && position.cmplt(max).all()
+            && tilemap.properties_at(position).walkable
+            && position.distance(self.safe_zone_center) > self.safe_zone_radius
+    }
+
+    fn pick_kind(self: &Self, rng: &mut ThreadRng) -> EnemyKind {
+        if rng.gen_bool(self.curve.orbit_chance(self.wave) as f64) {
+            *self.curve.shooters.choose(rng).unwrap_or(&EnemyKind::Ghost)
+        } else {
+            EnemyKind::Ghost
+        }
+    }
+}
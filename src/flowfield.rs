@@ -0,0 +1,115 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use glam::{IVec2, Vec2};
+
+use crate::render::Tilemap;
+
+const UNREACHABLE: u32 = u32::MAX;
+
+// cost of one step onto a tile of normal speed
+const STEP_COST: f32 = 10.0;
+
+// travel cost from every tile to the target tile, shared by the whole horde so
+// enemies route around walls and slow terrain without running pathfinding per enemy
+pub struct FlowField {
+    width: usize,
+    height: usize,
+    target: Option<IVec2>,
+    distances: Vec<u32>,
+}
+
+impl FlowField {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            target: None,
+            distances: vec![UNREACHABLE; width * height],
+        }
+    }
+
+    // recompute only when target moved to another tile
+    pub fn update(self: &mut Self, tilemap: &Tilemap, target: IVec2) {
+        if self.target == Some(target) {
+            return;
+        }
+        self.target = Some(target);
+        self.distances.fill(UNREACHABLE);
+        let Some(start) = self.index(target) else {
+            return;
+        };
+        // target tile is seeded even if it isn't walkable
+        self.distances[start] = 0;
+        let mut queue = BinaryHeap::from([Reverse((0, start))]);
+        while let Some(Reverse((distance, index))) = queue.pop() {
+            if distance > self.distances[index] {
+                continue;
+            }
+            let tile = IVec2::new((index / self.height) as i32, (index % self.height) as i32);
+            for next in tilemap.neighbours(tile) {
+                let Some(next_index) = self.index(next) else {
+                    continue;
+                };
+                let properties = tilemap.properties(next);
+                if !properties.walkable {
+                    continue;
+                }
+                let next_distance =
+                    distance + (STEP_COST / properties.speed_multiplier).round() as u32;
+                if next_distance < self.distances[next_index] {
+                    self.distances[next_index] = next_distance;
+                    queue.push(Reverse((next_distance, next_index)));
+                }
+            }
+        }
+    }
+
+    // normalized direction toward the neighbour closest to the target,
+    // None on target tile or when there is no way to get there
+    pub fn direction(self: &Self, tile: IVec2) -> Option<Vec2> {
+        let current = self.distance(tile);
+        if current == 0 {
+            return None;
+        }
+        let mut best = None;
+        let mut best_distance = current;
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let offset = IVec2::new(x, y);
+                if offset == IVec2::ZERO {
+                    continue;
+                }
+                // don't cut corners through walls
+                if x != 0
+                    && y != 0
+                    && (self.distance(tile + IVec2::new(x, 0)) == UNREACHABLE
+                        || self.distance(tile + IVec2::new(0, y)) == UNREACHABLE)
+                {
+                    continue;
+                }
+                let distance = self.distance(tile + offset);
+                if distance < best_distance {
+                    best_distance = distance;
+                    best = Some(offset);
+                }
+            }
+        }
+        best.map(|offset| offset.as_vec2().normalize())
+    }
+
+    fn distance(self: &Self, tile: IVec2) -> u32 {
+        self.index(tile)
+            .map_or(UNREACHABLE, |index| self.distances[index])
+    }
+
+    fn index(self: &Self, tile: IVec2) -> Option<usize> {
+        if tile.x < 0
+            || tile.y < 0
+            || tile.x as usize >= self.width
+            || tile.y as usize >= self.height
+        {
+            return None;
+        }
+        Some(tile.x as usize * self.height + tile.y as usize)
+    }
+}
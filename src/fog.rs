@@ -0,0 +1,162 @@
+use glam::{IVec2, Vec2};
+
+use crate::render::{Tilemap, CHUNK_SIZE};
+
+// tiles the player has seen and tiles in sight right now, a grid over the tilemap that
+// scrolls along with it in endless worlds
+pub struct Fog {
+    pub sight_radius: f32,
+    pub blocked_by_terrain: bool, // tiles blocking bullets block sight too
+    width: usize,
+    height: usize,
+    origin: IVec2, // world tile of the first one, tilemap position over tile size
+    explored: Vec<bool>,
+    visible: Vec<bool>,
+    visible_tiles: Vec<usize>, // indices set in visible, so clearing them is cheap
+    explored_count: usize,     // every tile explored this run, scrolled away ones too
+    center: Option<IVec2>,     // tile sight was last computed from
+    version: u32,              // bumped when more of the map gets explored
+}
+
+impl Fog {
+    pub fn new(tilemap: &Tilemap, sight_radius: f32, blocked_by_terrain: bool) -> Self {
+        let (width, height) = (tilemap.width(), tilemap.height());
+        return Self {
+            sight_radius,
+            blocked_by_terrain,
+            width,
+            height,
+            origin: Self::origin(tilemap),
+            explored: vec![false; width * height],
+            visible: vec![false; width * height],
+            visible_tiles: vec![],
+            explored_count: 0,
+            center: None,
+            version: 0,
+        };
+    }
+
+    // recomputes what's in sight when the viewer moved to another tile
+    pub fn update(self: &mut Self, tilemap: &Tilemap, viewer: Vec2) {
+        let center = tilemap.world_to_tile(viewer);
+        if self.center == Some(center) {
+            return;
+        }
+        self.center = Some(center);
+        for index in self.visible_tiles.drain(..) {
+            self.visible[index] = false;
+        }
+        let radius = (self.sight_radius / tilemap.tile_width as f32).ceil() as i32;
+        let tile_center = Vec2::new(tilemap.tile_width as f32, tilemap.tile_height as f32) / 2.0;
+        let mut explored = false;
+        for tile in tilemap.region(center - radius, center + radius) {
+            let target = tilemap.tile_to_world(tile) + tile_center;
+            if target.distance(viewer) > self.sight_radius {
+                continue;
+            }
+            // the line stops a tile short so walls facing the viewer are seen too
+            let short = target + (viewer - target).clamp_length_max(tilemap.tile_width as f32);
+            if self.blocked_by_terrain && !tilemap.line_of_sight(viewer, short) {
+                continue;
+            }
+            let Some(index) = self.index(tile) else {
+                continue;
+            };
+            self.visible[index] = true;
+            self.visible_tiles.push(index);
+            if !self.explored[index] {
+                self.explored[index] = true;
+                self.explored_count += 1;
+                explored = true;
+            }
+        }
+        if explored {
+            self.version += 1;
+        }
+    }
+
+    // moves the grid along with a scrolled tilemap, tiles scrolled in start unexplored
+    pub fn follow(self: &mut Self, tilemap: &Tilemap) {
+        let origin = Self::origin(tilemap);
+        let offset = origin - self.origin;
+        if offset == IVec2::ZERO {
+            return;
+        }
+        let mut explored = vec![false; self.width * self.height];
+        for x in 0..self.width as i32 {
+            for y in 0..self.height as i32 {
+                if let Some(from) = self.index(IVec2::new(x, y) + offset) {
+                    explored[x as usize * self.height + y as usize] = self.explored[from];
+                }
+            }
+        }
+        self.explored = explored;
+        for index in self.visible_tiles.drain(..) {
+            self.visible[index] = false;
+        }
+        self.origin = origin;
+        self.center = None;
+        self.version += 1;
+    }
+
+    // explored tiles of a world chunk as bits, x major like chunk storage, None when
+    // the grid doesn't cover it
+    pub fn chunk_mask(self: &Self, chunk: IVec2) -> Option<u64> {
+        let first = chunk * CHUNK_SIZE as i32 - self.origin;
+        let mut mask = 0;
+        for x in 0..CHUNK_SIZE as i32 {
+            for y in 0..CHUNK_SIZE as i32 {
+                if self.explored[self.index(first + IVec2::new(x, y))?] {
+                    mask |= 1 << (x * CHUNK_SIZE as i32 + y);
+                }
+            }
+        }
+        return Some(mask);
+    }
+    // restores a chunk's explored tiles, they were counted when first explored
+    pub fn set_chunk_mask(self: &mut Self, chunk: IVec2, mask: u64) {
+        let first = chunk * CHUNK_SIZE as i32 - self.origin;
+        for x in 0..CHUNK_SIZE as i32 {
+            for y in 0..CHUNK_SIZE as i32 {
+                if let Some(index) = self.index(first + IVec2::new(x, y)) {
+                    self.explored[index] = mask & 1 << (x * CHUNK_SIZE as i32 + y) != 0;
+                }
+            }
+        }
+        self.version += 1;
+    }
+
+    pub fn is_explored(self: &Self, tile: IVec2) -> bool {
+        return self.index(tile).map_or(false, |index| self.explored[index]);
+    }
+    pub fn is_visible(self: &Self, tile: IVec2) -> bool {
+        return self.index(tile).map_or(false, |index| self.visible[index]);
+    }
+    // tiles explored this run
+    pub fn explored_count(self: &Self) -> usize {
+        return self.explored_count;
+    }
+    // share of the grid explored in percent
+    pub fn explored_percent(self: &Self) -> f32 {
+        let explored = self.explored.iter().filter(|explored| **explored).count();
+        return explored as f32 / (self.width * self.height) as f32 * 100.0;
+    }
+    pub fn version(self: &Self) -> u32 {
+        return self.version;
+    }
+
+    fn index(self: &Self, tile: IVec2) -> Option<usize> {
+        if tile.x < 0
+            || tile.y < 0
+            || tile.x as usize >= self.width
+            || tile.y as usize >= self.height
+        {
+            return None;
+        }
+        return Some(tile.x as usize * self.height + tile.y as usize);
+    }
+    fn origin(tilemap: &Tilemap) -> IVec2 {
+        return tilemap.position()
+            / IVec2::new(tilemap.tile_width as i32, tilemap.tile_height as i32);
+    }
+}
@@ -8,6 +8,8 @@ pub struct InputState {
     pub attack: bool,
     pub dash: bool,
     pub quit: bool,
+    pub export_map: bool,
+    pub toggle_map: bool,
 
     l: bool, //left
     r: bool, //right
@@ -23,6 +25,8 @@ impl InputState {
             attack: false,
             dash: false,
             quit: false,
+            export_map: false,
+            toggle_map: false,
             l: false,
             r: false,
             u: false,
@@ -33,6 +37,8 @@ impl InputState {
     pub fn handle_events(self: &mut Self, pump: &mut EventPump) {
         self.dash = false;
         self.attack = false;
+        self.export_map = false;
+        self.toggle_map = false;
         self.movement = Vec2::ZERO;
 
         for event in pump.poll_iter() {
@@ -46,6 +52,15 @@ impl InputState {
                     keycode: Some(Keycode::Space),
                     ..
                 } => self.dash = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => self.export_map = true,
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => self.toggle_map = true,
                 Event::KeyDown {
                     keycode: Some(Keycode::W),
                     ..
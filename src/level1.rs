@@ -1,54 +1,155 @@
-use bracket_noise::prelude::{FastNoise, NoiseType};
-use glam::{UVec2, Vec2};
+use std::collections::HashMap;
+
+use glam::{IVec2, UVec2, Vec2};
 use rand::{rngs::ThreadRng, Rng};
 use sdl2::{render::TextureCreator, video::WindowContext};
 use sdl2_animation::{Animation, Keyframe};
 
 use crate::{
-    components::{self, BulletType},
+    activity::activity_dt,
+    ai,
+    behaviour_tree::{BehaviourTree, Behaviours, Blackboard},
+    bullet_pattern::BulletPattern,
+    collision::{self, Contact},
+    components::{self, Faction},
+    director::{DifficultyCurve, Director, EnemyKind, SpawnPoint, SpawnRequest},
+    flowfield::FlowField,
+    fog::Fog,
     input::InputState,
+    minimap::{Markers, Minimap},
     player_state,
-    render::{Camera, Tile, Tilemap},
+    projectile::Projectiles,
+    prop::Prop,
+    render::{Camera, Tilemap, TilemapRenderer},
+    season::Season,
+    spatial::SpatialGrid,
+    stats::RunStats,
+    stream::{ChunkEntity, WorldStream},
     texturemanager::TextureManager,
+    tiled::{self, Spawn, SpawnKind},
+    worldgen,
     Level, systems::system_camera_follow, SCREEN_WIDTH, SCREEN_HEIGHT,
 };
 
 const MOB_LIMIT: u32 = 320;
+const HOMING_RADIUS: f32 = 512.0;
+const SIGHT_RANGE: f32 = 1024.0;
+const EXPORT_PATH: &str = "maps/exported.tmj";
+const BEHAVIOUR_DIR: &str = "res/behaviour";
+// how far the player sees, tiles further away or behind walls stay in the fog
+const SIGHT_RADIUS: f32 = 576.0;
+// darkness over explored tiles out of sight, unexplored ones are black
+const FOG_ALPHA: u8 = 120;
+
+// where the level's map comes from
+#[derive(Clone)]
+pub enum MapSource {
+    Generated,
+    Tiled(String),
+    Endless, // streamed in chunks around the player, no point crystal count wins it
+}
+
+impl MapSource {
+    fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Generated => "generated",
+            Self::Tiled(_) => "tiled",
+            Self::Endless => "endless",
+        }
+    }
+}
+
 pub struct Level1State<'a> {
     update_started: bool,
+    pub season: Season,
     texture_creator: TextureCreator<WindowContext>,
     texture_manager: TextureManager,
     world: hecs::World,
     camera: Camera,
     tilemap: Tilemap,
+    source: MapSource,
+    stream: Option<WorldStream>,
+    spawns: Vec<Spawn>, // placed on the first update
+    tilemap_renderer: TilemapRenderer,
+    minimap: Minimap,
+    fog: Fog,
+    flow_field: FlowField,
     points: u32,
+    run_time: f32, // seconds since the level started
     player_state_input: player_state::Input,
-    enemy_spawner_timer: f32,
+    director: Director,
+    behaviours: Behaviours,
+    projectiles: Projectiles,
     music: sdl2::mixer::Music<'a>,
     sound_dash: sdl2::mixer::Chunk,
     sound_shoot: sdl2::mixer::Chunk,
     sound_crystal: sdl2::mixer::Chunk,
     player_death: bool,
     mob_count: u32,
+    player_shots: Vec<Vec2>,
+    enemy_grid: SpatialGrid,
+    collider_grid: SpatialGrid,
+    static_grid: SpatialGrid,
+    obstacle_grid: SpatialGrid, // solid props, nothing walks through them
+    contacts: Vec<Contact>,
     particles_state: sdl2_particles::ParticlesState,
 }
 
 impl<'a> Level1State<'a> {
-    pub fn new(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) -> Self {
-        let music = sdl2::mixer::Music::from_file("res/music.wav").unwrap();
+    pub fn new(
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+        season: Season,
+        source: MapSource,
+    ) -> Self {
+        let music = sdl2::mixer::Music::from_file(season.music()).unwrap();
         let sound_dash = sdl2::mixer::Chunk::from_file("res/dash.wav").unwrap();
         let mut sound_shoot = sdl2::mixer::Chunk::from_file("res/shoot.wav").unwrap();
         sound_shoot.set_volume(50);
         let sound_crystal = sdl2::mixer::Chunk::from_file("res/crystal.wav").unwrap();
+        let generate = || {
+            let level = worldgen::generate(rand::random(), &season.worldgen());
+            (level.tilemap, level.spawns)
+        };
+        let mut stream = None;
+        let (tilemap, spawns) = match &source {
+            MapSource::Generated => generate(),
+            MapSource::Tiled(path) => match tiled::load(path) {
+                Ok(map) => (map.tilemap, map.spawns),
+                Err(error) => {
+                    println!("Couldn't load map {}, generating one instead", error);
+                    generate()
+                }
+            },
+            MapSource::Endless => {
+                let (world_stream, tilemap, spawns) =
+                    WorldStream::new(rand::random(), season.worldgen());
+                stream = Some(world_stream);
+                (tilemap, spawns)
+            }
+        };
         Self {
             update_started: false,
+            season,
             texture_creator: canvas.texture_creator(),
             texture_manager: TextureManager::new(),
             world: hecs::World::new(),
             camera: Camera::new(),
-            tilemap: Tilemap::new(200, 200, 64, 64),
+            flow_field: FlowField::new(tilemap.width(), tilemap.height()),
+            fog: Fog::new(&tilemap, SIGHT_RADIUS, true),
+            tilemap,
+            source,
+            stream,
+            spawns,
+            tilemap_renderer: TilemapRenderer::new(),
+            minimap: Minimap::new(),
             points: 0,
-            enemy_spawner_timer: 0.0,
+            run_time: 0.0,
+            director: Director::new(DifficultyCurve {
+                max_cap: MOB_LIMIT,
+                ..season.difficulty()
+            }),
+            behaviours: Behaviours::load(BEHAVIOUR_DIR),
+            projectiles: Projectiles::new(),
             music,
             sound_dash,
             sound_shoot,
@@ -56,6 +157,12 @@ impl<'a> Level1State<'a> {
             player_state_input: player_state::Input::Nothing,
             player_death: false,
             mob_count: 0,
+            player_shots: vec![],
+            enemy_grid: SpatialGrid::new(128.0),
+            collider_grid: SpatialGrid::new(128.0),
+            static_grid: SpatialGrid::new(128.0),
+            obstacle_grid: SpatialGrid::new(128.0),
+            contacts: vec![],
             particles_state: sdl2_particles::ParticlesState::init(100),
         }
     }
@@ -69,8 +176,7 @@ pub fn update(
 ) {
     puffin::profile_scope!("update");
     let mut rng = rand::thread_rng();
-    let map_bound_x = -state.tilemap.position().x as f32;
-    let map_bound_y = -state.tilemap.position().y as f32;
+    state.run_time += dt;
     if !state.update_started {
         state.update_started = true;
 
@@ -95,7 +201,7 @@ pub fn update(
         ];
         let mut player_animation_state = components::Animation::default();
         player_animation_state.state.play(&idle_animation_player);
-        let _player = state.world.spawn((
+        let player = state.world.spawn((
             components::Player::default(),
             components::Transform::default(),
             components::Sprite {
@@ -104,202 +210,122 @@ pub fn update(
             },
             components::CameraTarget,
             components::PlayerController::default(),
+            components::Collider::player(),
+            Faction::Player,
+            components::Swept {
+                previous_position: Vec2::ZERO,
+            },
             player_animation_state,
         ));
-        // perlin generate water
-        let mut noise = FastNoise::seeded(rng.gen());
-        noise.set_noise_type(NoiseType::Perlin);
-        noise.set_frequency(0.05);
-        let max_x = state.tilemap.values.len();
-        let max_y = state.tilemap.values.get(0).unwrap().len();
-        for x in 0..max_x {
-            for y in 0..max_y {
-                let value = noise.get_noise(x.clone() as f32, y.clone() as f32);
-                if value.abs() < 0.07 {
-                    state.tilemap.set(
-                        x,
-                        y,
-                        Some(Tile {
-                            filename: "res/path.png",
-                        }),
-                    );
-                }
-                if value.abs() > 0.3 {
-                    state.tilemap.set(
-                        x,
-                        y,
-                        Some(Tile {
-                            filename: "res/grass.png",
-                        }),
-                    );
-                }
-            }
-        }
-        let mut dash_crystal_count = 0;
-        while dash_crystal_count <= 800 {
-            let mut position = Vec2::new(
-                rng.gen_range(-map_bound_x..map_bound_x),
-                rng.gen_range(-map_bound_y..map_bound_y),
-            );
-            let mut should_create_next_one = true;
-            while should_create_next_one {
-                dash_crystal_count += 1;
-                let random_dir = match rng.gen_range(1..=8) {
-                    1 => Vec2::new(1.0, 0.0),            //
-                    2 => Vec2::new(1.0, 1.0),            //
-                    3 => Vec2::new(0.0, 1.0),            //
-                    4 => Vec2::new(-1.0, 1.0),           //
-                    5 => Vec2::new(-1.0, 0.0),           //
-                    6 => Vec2::new(-1.0, -1.0),          //
-                    7 => Vec2::new(0.0, -1.0),           //
-                    8 => Vec2::new(1.0, -1.0),           //
-                    _ => panic!("WTF random is broken"), //
-                }
-                .normalize();
-                let distance = 400.0;
-                position += random_dir * distance;
-                create_dash_crystal_on(state, position.x as i32, position.y as i32);
-
-                should_create_next_one = rng.gen_bool(0.50);
-            }
+        if state.season.overheats() {
+            let _ = state.world.insert_one(player, components::Heat::default());
         }
+        let spawns = std::mem::take(&mut state.spawns);
+        create_spawns(state, &spawns);
+        for spawn in state
+            .director
+            .populate(MOB_LIMIT / 4, &state.tilemap, &mut rng)
         {
-            // GENERATE CRYSTALS IN CORNERS
-            let map_size_x = 199;
-            let map_size_y = 199;
-            let mut max_x = 5;
-            let mut max_y = 5;
-            loop {
-                let try_x = rng.gen_range(0..=max_x);
-                let try_y = rng.gen_range(0..=max_y);
-                if let Some(Tile { filename }) = state.tilemap.get(try_x, try_y) {
-                    if filename == &"res/path.png" {
-                        create_point_crystal_on(
-                            state,
-                            try_x as i32 * state.tilemap.tile_width as i32 * 2
-                                + state.tilemap.position().x,
-                            try_y as i32 * state.tilemap.tile_height as i32 * 2
-                                + state.tilemap.position().y,
-                        );
-                        break;
-                    }
-                }
-                max_x += 1;
-                max_x = max_x.clamp(0, map_size_x);
-                max_y += 1;
-                max_y = max_y.clamp(0, map_size_y);
-            }
-            let mut max_x = 5;
-            let mut max_y = 5;
-            loop {
-                let try_x = rng.gen_range(0..=max_x);
-                let try_y = rng.gen_range(0..=max_y);
-                if let Some(Tile { filename }) = state.tilemap.get(map_size_x - try_x, try_y) {
-                    if filename == &"res/path.png" {
-                        create_point_crystal_on(
-                            state,
-                            (map_size_x - try_x) as i32 * state.tilemap.tile_width as i32 * 2
-                                + state.tilemap.position().x,
-                            try_y as i32 * state.tilemap.tile_height as i32 * 2
-                                + state.tilemap.position().y,
-                        );
-                        break;
-                    }
-                }
-                max_x += 1;
-                max_x = max_x.clamp(0, map_size_x);
-                max_y += 1;
-                max_y = max_y.clamp(0, map_size_y);
-            }
-            let mut max_x = 5;
-            let mut max_y = 5;
-            loop {
-                let try_x = rng.gen_range(0..=max_x);
-                let try_y = rng.gen_range(0..=max_y);
-                if let Some(Tile { filename }) =
-                    state.tilemap.get(map_size_x - try_x, map_size_y - try_y)
-                {
-                    if filename == &"res/path.png" {
-                        create_point_crystal_on(
-                            state,
-                            (map_size_x - try_x) as i32 * state.tilemap.tile_width as i32 * 2
-                                + state.tilemap.position().x,
-                            (map_size_y - try_y) as i32 * state.tilemap.tile_height as i32 * 2
-                                + state.tilemap.position().y,
-                        );
-                        break;
-                    }
-                }
-                max_x += 1;
-                max_x = max_x.clamp(0, map_size_x);
-                max_y += 1;
-                max_y = max_y.clamp(0, map_size_y);
-            }
-        }
-        for _ in 0..MOB_LIMIT / 4 {
             state.mob_count += 1;
-            create_enemy_on(
-                &mut state.world,
-                rng.gen_range(-map_bound_x as i32..map_bound_x as i32),
-                rng.gen_range(-map_bound_y as i32..map_bound_y as i32),
-                &mut rng,
-            );
+            create_enemy_on(&mut state.world, &state.behaviours, &spawn, &mut rng);
         }
     }
+    if input_state.export_map {
+        export_map(state);
+    }
+    if input_state.toggle_map {
+        state.minimap.fullscreen = !state.minimap.fullscreen;
+    }
     // Update
     // Reset player input state
     state.player_state_input = player_state::Input::Nothing;
+    state.player_shots.clear();
+    system_swept(&mut state.world);
 
-    // Spawn enemies every second
-    state.enemy_spawner_timer -= dt;
-    if state.enemy_spawner_timer <= 0.0 && state.mob_count <= MOB_LIMIT {
-        state.mob_count += 1;
-        let enemy_spawn_cooldown = 1.0;
-        state.enemy_spawner_timer = enemy_spawn_cooldown;
-        create_enemy_on(
-            &mut state.world,
-            rng.gen_range(-map_bound_x as i32..map_bound_x as i32),
-            rng.gen_range(-map_bound_y as i32..map_bound_y as i32),
-            &mut rng,
-        );
-    }
+    // Spawn enemies in waves
+    system_director(state, &mut rng, dt);
     state
         .particles_state
         .update(std::time::Duration::from_secs_f32(dt));
-    system_player_controller(
+    system_player_controller(state, input_state, &mut rng, dt);
+    system_flow_field(&state.world, &state.tilemap, &mut state.flow_field);
+    ai::system_activity(&mut state.world, dt);
+    ai::system_awareness(
         &mut state.world,
-        &mut state.player_state_input,
-        &mut state.particles_state,
-        &state.sound_shoot,
-        &state.camera,
-        input_state,
+        &state.tilemap,
+        &state.obstacle_grid,
+        &state.player_shots,
         &mut rng,
         dt,
     );
-    system_ghost_ai(&mut state.world, &mut state.player_death, dt);
-    system_shooting_enemies(state, dt);
-    system_orbit_ai(state, dt);
-    system_crystal(
+    ai::system_ghost_ai(
         &mut state.world,
-        &mut state.player_state_input,
+        &state.enemy_grid,
+        &state.flow_field,
+        &state.tilemap,
+        &state.obstacle_grid,
+        dt,
+    );
+    for (projectile, position, direction, faction) in
+        ai::system_shooting_enemies(&mut state.world, &state.projectiles, dt)
+    {
+        create_bullet(
+            &mut state.world,
+            &state.projectiles,
+            projectile,
+            position,
+            direction,
+            faction,
+        );
+    }
+    ai::system_orbit_ai(
+        &mut state.world,
+        &state.flow_field,
+        &state.tilemap,
+        &state.obstacle_grid,
+        dt,
+    );
+    system_behaviour_tree(state, &mut rng, dt);
+    system_lifetime(&mut state.world, dt);
+    system_move_bullets(&mut state.world, &state.tilemap, &state.enemy_grid, dt);
+    system_broadphase(&state.world, &mut state.enemy_grid);
+    system_collision(
+        &state.world,
+        &mut state.collider_grid,
+        &state.static_grid,
+        &mut state.contacts,
+    );
+    system_contact_damage(&mut state.world, &state.contacts, &mut state.player_death);
+    system_terrain_damage(&mut state.world, &state.tilemap, &mut state.player_death);
+    system_heat(
+        &mut state.world,
+        &state.tilemap,
         &mut state.particles_state,
-        &mut state.points,
-        &state.sound_crystal,
+        &mut state.player_death,
         &mut rng,
+        dt,
     );
+    system_crystal(state, &mut rng);
+    system_regrowth(state, dt);
     system_bullets(
         &mut state.world,
+        &state.contacts,
         &mut state.player_death,
         &mut state.mob_count,
-        dt,
     );
     system_camera_follow(&state.world, &mut state.camera, dt);
-    system_animation(&mut state.world, dt);
-    if state.points >= 3 {
-        *level = Level::ResetLevel1;
+    system_stream(state, &mut rng);
+    system_fog(state);
+    system_discovery(&mut state.world, &state.tilemap, &state.fog);
+    ai::system_animation(&mut state.world, dt);
+    if state.points >= 3 && state.stream.is_none() {
+        run_stats(state, true).save();
+        *level = Level::WonLevel1;
     }
     if state.player_death {
-        *state = Level1State::new(canvas);
+        run_stats(state, false).save();
+        let source = state.source.clone();
+        *state = Level1State::new(canvas, state.season, source);
         *level = Level::Menu;
     }
     for (_id, player) in state.world.query_mut::<&mut components::Player>() {
@@ -314,58 +340,29 @@ pub fn update(
 
 pub fn render(state: &mut Level1State, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
     puffin::profile_scope!("render");
-    canvas.set_draw_color(sdl2::pixels::Color::RGB(39, 9, 31));
+    canvas.set_draw_color(state.season.background());
     canvas.clear();
     // render tilemap
-    'xses_loop: for (x, xses) in state
-        .tilemap
-        .values
-        .iter()
-        .enumerate(){
-        'yses_loop: for (y, tile) in xses.iter()
-            .map(|f| f.as_ref())
-            .enumerate() {
-            if let Some(tile) = tile {
-                let texture = state
-                    .texture_manager
-                    .texture(tile.filename, &state.texture_creator);
-                let dst = sdl2::rect::Rect::new(
-                    state.tilemap.position().x
-                        + state.camera.x()
-                        + (state.tilemap.tile_width) as i32 * x as i32,
-                    state.tilemap.position().y
-                        + state.camera.y()
-                        + (state.tilemap.tile_height) as i32 * y as i32,
-                    state.tilemap.tile_width,
-                    state.tilemap.tile_height,
-                );
-                // render only if dst is in screen bounds + offset
-                let offset = 100;
-                if dst.x >= offset + SCREEN_WIDTH as i32{
-                    break 'xses_loop;
-                }
-                if dst.y >= offset + SCREEN_HEIGHT as i32{
-                    break 'yses_loop;
-                }
-                if dst.x < -offset{
-                    continue 'xses_loop;
-                }
-                if dst.y < -offset{
-                    continue 'yses_loop;
-                }
-                let _ = canvas.copy(texture, None, dst);
-            }
-        }
-    }
+    state.tilemap_renderer.render(
+        &state.tilemap,
+        &state.camera,
+        canvas,
+        &mut state.texture_manager,
+        &state.texture_creator,
+    );
     // render particles
     state
         .particles_state
         .render_with_offset(state.camera.x(), state.camera.y(), canvas);
-    // render sprites
-    for (id, (sprite, transform)) in &mut state
-        .world
-        .query::<(&components::Sprite, &components::Transform)>()
-    {
+    // render sprites, y-sorted so whatever stands lower on screen is drawn in front,
+    // flat props lie under everything
+    let mut sprites = vec![];
+    for (_id, (sprite, transform, animation, scenery)) in &mut state.world.query::<(
+        &components::Sprite,
+        &components::Transform,
+        Option<&components::Animation>,
+        Option<&components::Scenery>,
+    )>() {
         let dst = sdl2::rect::Rect::new(
             state.camera.x() + transform.position.x as i32,
             state.camera.y() + transform.position.y as i32,
@@ -382,25 +379,96 @@ pub fn render(state: &mut Level1State, canvas: &mut sdl2::render::Canvas<sdl2::v
         )) {
             continue;
         }
-        let src = state
-            .world
-            .entity(id)
-            .unwrap()
-            .get::<&components::Animation>()
-            .and_then(|f| Some(f.state.get_src()));
+        let depth = if scenery.map_or(false, |scenery| scenery.prop.flat) {
+            i32::MIN
+        } else {
+            dst.bottom()
+        };
+        let src = animation.map(|animation| animation.state.get_src());
+        sprites.push((depth, sprite.filename, src, dst));
+    }
+    sprites.sort_by_key(|(depth, ..)| *depth);
+    for (_, filename, src, dst) in sprites {
         let texture = state
             .texture_manager
-            .texture(sprite.filename, &state.texture_creator);
+            .texture(filename, &state.texture_creator);
         let _ = canvas.copy(texture, src, dst);
     }
+    // fog of war over the tiles on screen
+    canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+    let (min, max) = state.tilemap.visible_range(&state.camera);
+    for tile in state.tilemap.region(min, max) {
+        let alpha = if !state.fog.is_explored(tile) {
+            255
+        } else if !state.fog.is_visible(tile) {
+            FOG_ALPHA
+        } else {
+            continue;
+        };
+        let mut rect = state.tilemap.tile_rect(tile);
+        rect.offset(state.camera.x(), state.camera.y());
+        canvas.set_draw_color(sdl2::pixels::Color::RGBA(0, 0, 0, alpha));
+        let _ = canvas.fill_rect(rect);
+    }
+    let markers = minimap_markers(&state.world, &state.tilemap, &state.fog);
+    state.minimap.update(
+        &state.tilemap,
+        &state.fog,
+        state.season.background(),
+        &state.texture_creator,
+    );
+    state
+        .minimap
+        .render(&state.tilemap, &state.camera, &markers, canvas);
     canvas.present();
 }
 
-fn player_damage(world: &mut hecs::World, player_death: &mut bool) {
+// the player, discovered point crystals and enemies in sight, marked at their middle
+fn minimap_markers(world: &hecs::World, tilemap: &Tilemap, fog: &Fog) -> Markers {
+    let mut markers = Markers {
+        player: None,
+        crystals: vec![],
+        enemies: vec![],
+    };
+    for (_id, (transform, sprite, player, crystal, discovered, enemy)) in &mut world.query::<(
+        &components::Transform,
+        &components::Sprite,
+        Option<&components::Player>,
+        Option<&components::PointCrystal>,
+        Option<&components::Discovered>,
+        Option<&components::Enemy>,
+    )>() {
+        let center = transform.position + sprite.size.as_vec2() / 2.0;
+        match (player, crystal, discovered, enemy) {
+            (Some(_), _, _, _) => markers.player = Some(center),
+            (_, Some(_), Some(_), _) => markers.crystals.push(center),
+            (_, _, _, Some(_)) if fog.is_visible(tilemap.world_to_tile(center)) => {
+                markers.enemies.push(center)
+            }
+            _ => {}
+        }
+    }
+    return markers;
+}
+
+// the run so far, explored share of the map only means something for a fixed one
+fn run_stats(state: &Level1State, won: bool) -> RunStats {
+    return RunStats {
+        season: state.season.name(),
+        map: state.source.name(),
+        won,
+        duration: state.run_time,
+        points: state.points,
+        explored_percent: state.stream.is_none().then(|| state.fog.explored_percent()),
+        explored_tiles: state.fog.explored_count(),
+    };
+}
+
+fn player_damage(world: &mut hecs::World, damage: u8, player_death: &mut bool) {
     let cooldown = 0.5;
     for (_, player) in world.query_mut::<&mut components::Player>() {
         if player.invincibility_timer <= 0.0 {
-            player.lives -= 1;
+            player.lives = player.lives.saturating_sub(damage);
             player.invincibility_timer = cooldown;
             println!("Player took damage! Remaining lives: {}/3", &player.lives);
             if player.lives <= 0 {
@@ -410,323 +478,749 @@ fn player_damage(world: &mut hecs::World, player_death: &mut bool) {
     }
 }
 
-fn system_shooting_enemies(state: &mut Level1State, dt: f32) {
-    let mut optional_player_position = None;
-    let mut bullets_to_create = vec![];
-    for (_id, (transform, _)) in &mut state
+// objects of a generated or Tiled map, enemy spawns replace the director's random positions
+fn create_spawns(state: &mut Level1State, spawns: &[Spawn]) {
+    for spawn in spawns {
+        match spawn.kind {
+            SpawnKind::PlayerStart => {
+                for (_id, (transform, swept, _)) in state.world.query_mut::<(
+                    &mut components::Transform,
+                    &mut components::Swept,
+                    &components::Player,
+                )>() {
+                    transform.position = spawn.position;
+                    swept.previous_position = spawn.position;
+                }
+                state.director.safe_zone_center = spawn.position;
+            }
+            SpawnKind::PointCrystal => {
+                create_point_crystal_on(state, spawn.position.x as i32, spawn.position.y as i32)
+            }
+            SpawnKind::DashCrystal => {
+                create_dash_crystal_on(state, spawn.position.x as i32, spawn.position.y as i32)
+            }
+            SpawnKind::Enemy(kind) => state.director.spawn_points.push(SpawnPoint {
+                position: spawn.position,
+                kind,
+            }),
+            SpawnKind::Prop(prop) => create_prop_on(state, prop, spawn.position),
+        }
+    }
+}
+
+// current map and objects saved for editing in Tiled
+fn export_map(state: &Level1State) {
+    let mut spawns = vec![];
+    for (_id, (transform, player, point, dash, scenery)) in &mut state.world.query::<(
+        &components::Transform,
+        Option<&components::Player>,
+        Option<&components::PointCrystal>,
+        Option<&components::DashingCrystal>,
+        Option<&components::Scenery>,
+    )>() {
+        let kind = match (player, point, dash, scenery) {
+            (Some(_), _, _, _) => SpawnKind::PlayerStart,
+            (_, Some(_), _, _) => SpawnKind::PointCrystal,
+            (_, _, Some(_), _) => SpawnKind::DashCrystal,
+            (_, _, _, Some(scenery)) => SpawnKind::Prop(scenery.prop),
+            _ => continue,
+        };
+        spawns.push(Spawn {
+            kind,
+            position: transform.position,
+        });
+    }
+    for point in state.director.spawn_points.iter() {
+        spawns.push(Spawn {
+            kind: SpawnKind::Enemy(point.kind),
+            position: point.position,
+        });
+    }
+    match tiled::save(EXPORT_PATH, &state.tilemap, &spawns) {
+        Ok(()) => println!("Map exported to {}", EXPORT_PATH),
+        Err(error) => println!("Couldn't export map {}", error),
+    }
+}
+
+fn system_director(state: &mut Level1State, rng: &mut ThreadRng, dt: f32) {
+    let mut optional_player = None;
+    for (_id, (transform, player)) in &mut state
         .world
         .query::<(&components::Transform, &components::Player)>()
     {
-        optional_player_position = Some(transform.position);
+        optional_player = Some((transform.position, player.lives));
+    }
+    if let Some((player_position, player_lives)) = optional_player {
+        let wave_count = state
+            .world
+            .query::<&components::WaveSpawned>()
+            .iter()
+            .count() as u32;
+        state
+            .director
+            .track_performance(dt, player_lives, state.mob_count);
+        let spawns = state.director.update(
+            dt,
+            player_position,
+            state.mob_count,
+            wave_count,
+            &state.tilemap,
+            rng,
+        );
+        for spawn in spawns {
+            state.mob_count += 1;
+            let enemy = create_enemy_on(&mut state.world, &state.behaviours, &spawn, rng);
+            let _ = state.world.insert_one(enemy, components::WaveSpawned);
+        }
     }
-    for (_id, (transform, shooting)) in state
+}
+
+// endless worlds scroll the tilemap after the player, what's in chunks left behind is
+// saved and unloaded and chunks ahead are loaded back or generated
+fn system_stream(state: &mut Level1State, rng: &mut ThreadRng) {
+    puffin::profile_scope!("system_stream");
+    let Some(stream) = &state.stream else {
+        return;
+    };
+    let mut optional_player = None;
+    for (_id, (transform, _)) in &mut state
         .world
-        .query_mut::<(&components::Transform, &mut components::ShootingEnemy)>()
+        .query::<(&components::Transform, &components::Player)>()
     {
-        shooting.timer -= dt;
-        if let Some(target_position) = optional_player_position {
-            let direction = target_position - transform.position;
-            if shooting.timer <= 0.0 && direction.length() <= shooting.range {
-                shooting.timer = shooting.cooldown;
-                bullets_to_create.push((
-                    transform.position,
-                    direction.normalize(),
-                    BulletType::FromEnemy,
-                ));
+        optional_player = Some(transform.position);
+    }
+    let Some(player_position) = optional_player else {
+        return;
+    };
+    let Some(scroll) = stream.update(&mut state.tilemap, player_position) else {
+        return;
+    };
+    let mut saved: HashMap<IVec2, Vec<ChunkEntity>> = scroll
+        .unloaded
+        .iter()
+        .map(|chunk| (*chunk, vec![]))
+        .collect();
+    let mut unloaded = vec![];
+    for (id, (transform, point, dash, scenery, regrowth, enemy)) in &mut state.world.query::<(
+        &components::Transform,
+        Option<&components::PointCrystal>,
+        Option<&components::DashingCrystal>,
+        Option<&components::Scenery>,
+        Option<&components::Regrowth>,
+        Option<&components::Enemy>,
+    )>() {
+        let Some(chunk) = scroll.unloaded_chunk(transform.position) else {
+            continue;
+        };
+        let position = transform.position;
+        let spawn = |kind| ChunkEntity::Spawn(Spawn { kind, position });
+        let entity = match (point, dash, scenery, regrowth, enemy) {
+            (Some(_), _, _, _, _) => spawn(SpawnKind::PointCrystal),
+            (_, Some(_), _, _, _) => spawn(SpawnKind::DashCrystal),
+            (_, _, Some(scenery), _, _) => spawn(SpawnKind::Prop(scenery.prop)),
+            (_, _, _, Some(regrowth), _) => ChunkEntity::Regrowth(position, regrowth.timer),
+            (_, _, _, _, Some(enemy)) => ChunkEntity::Enemy(enemy.kind, position),
+            _ => continue,
+        };
+        saved.entry(chunk).or_default().push(entity);
+        unloaded.push(id);
+    }
+    for id in unloaded {
+        if let Ok(entity) = state.world.entity(id) {
+            if let (Some(transform), Some(collider)) = (
+                entity.get::<&components::Transform>(),
+                entity.get::<&components::Collider>(),
+            ) {
+                if collider.is_static {
+                    collision::remove(&mut state.static_grid, id, &collider, transform.position);
+                    collision::remove(&mut state.obstacle_grid, id, &collider, transform.position);
+                }
+            }
+            if entity.has::<components::Enemy>() {
+                state.mob_count = state.mob_count.saturating_sub(1);
             }
         }
+        let _ = state.world.despawn(id);
     }
-    for bullet in bullets_to_create {
-        create_bullet(&mut state.world, bullet.0, bullet.1, bullet.2);
+    state.director.spawn_points.retain(|point| {
+        let Some(chunk) = scroll.unloaded_chunk(point.position) else {
+            return true;
+        };
+        saved
+            .entry(chunk)
+            .or_default()
+            .push(ChunkEntity::Spawn(Spawn {
+                kind: SpawnKind::Enemy(point.kind),
+                position: point.position,
+            }));
+        false
+    });
+    for (chunk, entities) in saved.iter() {
+        // the fog grid still covers the unloaded chunks until it follows the tilemap
+        let explored = state.fog.chunk_mask(*chunk).unwrap_or(0);
+        stream.save(*chunk, entities, explored);
     }
+    state.fog.follow(&state.tilemap);
+    for (chunk, explored) in scroll.explored.iter() {
+        state.fog.set_chunk_mask(*chunk, *explored);
+    }
+    let mut spawns = vec![];
+    for entity in scroll.loaded {
+        match entity {
+            ChunkEntity::Spawn(spawn) => spawns.push(spawn),
+            ChunkEntity::Enemy(kind, position) => {
+                state.mob_count += 1;
+                let spawn = SpawnRequest {
+                    position,
+                    kind,
+                    detection_radius: None,
+                };
+                create_enemy_on(&mut state.world, &state.behaviours, &spawn, rng);
+            }
+            ChunkEntity::Regrowth(position, timer) => {
+                state.world.spawn((
+                    components::Transform::with_position(position.x, position.y),
+                    components::Regrowth { timer },
+                ));
+            }
+        }
+    }
+    create_spawns(state, &spawns);
 }
 
-fn system_crystal(
-    world: &mut hecs::World,
-    player_state_input: &mut player_state::Input,
-    particles_state: &mut sdl2_particles::ParticlesState,
-    points: &mut u32,
-    sound_crystal: &sdl2::mixer::Chunk,
-    rng: &mut ThreadRng,
-) {
-    let mut optional_player_position = None;
-    let mut optional_player_size = None;
-    for (_id, (transform, sprite, _)) in &mut world.query::<(
-        &components::Transform,
-        &components::Sprite,
-        &components::PlayerController,
-    )>() {
-        optional_player_position = Some(transform.position);
-        optional_player_size = Some(sprite.size);
-    }
+fn system_crystal(state: &mut Level1State, rng: &mut ThreadRng) {
+    puffin::profile_scope!("system_crystal");
     let mut crystals_to_delete = vec![];
-    let mut should_be_stopped = false;
-    if let (Some(target_position), Some(target_size)) =
-        (optional_player_position, optional_player_size)
-    {
-        // if target exist
+    // player and when along their movement they hit a dash crystal
+    let mut stopped_at = None;
+    for contact in state.contacts.iter() {
+        let (Ok(player), Ok(crystal)) =
+            (state.world.entity(contact.a), state.world.entity(contact.b))
+        else {
+            continue;
+        };
+        if !player.has::<components::PlayerController>() || crystals_to_delete.contains(&contact.b)
+        {
+            continue;
+        }
+        // state.contacts are ordered by time, the player never reaches what is behind a dash crystal
+        if stopped_at.map_or(false, |(_, time)| contact.time > time) {
+            break;
+        }
         // dash crystal
-        for (crystal_id, (transform, sprite, _)) in &mut world.query::<(
-            &components::Transform,
-            &components::Sprite,
-            &components::DashingCrystal,
-        )>() {
-            if sdl2::rect::Rect::new(
-                target_position.x as i32,
-                target_position.y as i32,
-                target_size.x,
-                target_size.y,
-            )
-            .has_intersection(sdl2::rect::Rect::new(
-                transform.position.x as i32,
-                transform.position.y as i32,
-                sprite.size.x,
-                sprite.size.y,
-            )) {
-                crystals_to_delete.push(crystal_id);
-                should_be_stopped = true;
+        if crystal.has::<components::DashingCrystal>() {
+            crystals_to_delete.push(contact.b);
+            stopped_at = Some((contact.a, contact.time));
+        }
+        // Point crystal
+        if crystal.has::<components::PointCrystal>() {
+            crystals_to_delete.push(contact.b);
+            let position = crystal
+                .get::<&components::Transform>()
+                .map_or(Vec2::ZERO, |transform| transform.position);
+            let _ = sdl2::mixer::Channel::all().play(&state.sound_crystal, 0);
+            for _ in 0..60 {
+                let particle_type = sdl2_particles::ParticleTypeBuilder::new(
+                    rng.gen_range(4..16),
+                    rng.gen_range(4..16),
+                    std::time::Duration::from_millis(rng.gen_range(200..300)),
+                )
+                .with_color(sdl2::pixels::Color::RGB(
+                    rng.gen_range(71..111),
+                    rng.gen_range(5..45),
+                    rng.gen_range(20..60),
+                )) // 91 25 40
+                .with_effect(sdl2_particles::ParticleEffect::LinearRotation {
+                    angular_velocity: 30.0,
+                })
+                .with_effect(sdl2_particles::ParticleEffect::FadeOut {
+                    delay: std::time::Duration::from_millis(150),
+                })
+                .with_effect(sdl2_particles::ParticleEffect::LinearMovement {
+                    velocity_x: rng.gen_range(-500.0..500.0),
+                    velocity_y: rng.gen_range(-500.0..500.0),
+                })
+                .build();
+                state
+                    .particles_state
+                    .emit(1, &particle_type, position.x + 40.0, position.y + 40.0);
             }
+            state.points += 1;
         }
-
-        if should_be_stopped {
-            *player_state_input = player_state::Input::Crystal;
+    }
+    if let Some((player, time)) = stopped_at {
+        state.player_state_input = player_state::Input::Crystal;
+        // dashing can carry the player far past the crystal within one frame
+        if let Ok((transform, swept)) = state
+            .world
+            .query_one_mut::<(&mut components::Transform, &components::Swept)>(player)
+        {
+            transform.position = swept.previous_position.lerp(transform.position, time);
         }
-        // Point crystal
-        for (crystal_id, (transform, sprite, _)) in &mut world.query::<(
-            &components::Transform,
-            &components::Sprite,
-            &components::PointCrystal,
-        )>() {
-            if sdl2::rect::Rect::new(
-                target_position.x as i32,
-                target_position.y as i32,
-                target_size.x,
-                target_size.y,
-            )
-            .has_intersection(sdl2::rect::Rect::new(
-                transform.position.x as i32,
-                transform.position.y as i32,
-                sprite.size.x,
-                sprite.size.y,
-            )) {
-                crystals_to_delete.push(crystal_id);
-                let _ = sdl2::mixer::Channel::all().play(sound_crystal, 0);
-                for _ in 0..60 {
-                    let particle_type = sdl2_particles::ParticleTypeBuilder::new(
-                        rng.gen_range(4..16),
-                        rng.gen_range(4..16),
-                        std::time::Duration::from_millis(rng.gen_range(200..300)),
-                    )
-                    .with_color(sdl2::pixels::Color::RGB(
-                        rng.gen_range(71..111),
-                        rng.gen_range(5..45),
-                        rng.gen_range(20..60),
-                    )) // 91 25 40
-                    .with_effect(sdl2_particles::ParticleEffect::LinearRotation {
-                        angular_velocity: 30.0,
-                    })
-                    .with_effect(sdl2_particles::ParticleEffect::FadeOut {
-                        delay: std::time::Duration::from_millis(150),
-                    })
-                    .with_effect(sdl2_particles::ParticleEffect::LinearMovement {
-                        velocity_x: rng.gen_range(-500.0..500.0),
-                        velocity_y: rng.gen_range(-500.0..500.0),
-                    })
-                    .build();
-                    particles_state.emit(
-                        1,
-                        &particle_type,
-                        transform.position.x + 40.0,
-                        transform.position.y + 40.0,
-                    );
+    }
+    let mut regrowing = vec![];
+    for crystal in crystals_to_delete {
+        if let Ok(entity) = state.world.entity(crystal) {
+            if let (Some(transform), Some(collider)) = (
+                entity.get::<&components::Transform>(),
+                entity.get::<&components::Collider>(),
+            ) {
+                collision::remove(
+                    &mut state.static_grid,
+                    crystal,
+                    &collider,
+                    transform.position,
+                );
+                if let Some(delay) = state.season.regrowth_delay() {
+                    if entity.has::<components::DashingCrystal>() {
+                        regrowing.push((transform.position, delay));
+                    }
                 }
-                *points += 1;
             }
         }
+        let _ = state.world.despawn(crystal);
     }
-    for crystal in crystals_to_delete.iter() {
-        let _ = world.despawn(*crystal);
+    for (position, delay) in regrowing {
+        state.world.spawn((
+            components::Transform::with_position(position.x, position.y),
+            components::Regrowth { timer: delay },
+        ));
     }
 }
 
-fn system_ghost_ai(world: &mut hecs::World, player_death: &mut bool, dt: f32) {
-    let mut optional_player_position = None;
-    let mut optional_player_size = None;
-    let mut should_die = false;
-    for (_id, (transform, sprite, _)) in &mut world.query::<(
+// what the player sees from the middle of their sprite
+fn system_fog(state: &mut Level1State) {
+    puffin::profile_scope!("system_fog");
+    for (_id, (transform, sprite, _)) in &mut state.world.query::<(
         &components::Transform,
         &components::Sprite,
         &components::Player,
     )>() {
-        optional_player_position = Some(transform.position);
-        optional_player_size = Some(sprite.size);
+        let center = transform.position + sprite.size.as_vec2() / 2.0;
+        state.fog.update(&state.tilemap, center);
     }
-    if let (Some(target_pos), Some(target_size)) = (optional_player_position, optional_player_size)
+}
+
+// point crystals that come in sight are marked on the minimap from then on
+fn system_discovery(world: &mut hecs::World, tilemap: &Tilemap, fog: &Fog) {
+    let mut discovered = vec![];
+    for (id, (transform, sprite, _, seen)) in world.query_mut::<(
+        &components::Transform,
+        &components::Sprite,
+        &components::PointCrystal,
+        Option<&components::Discovered>,
+    )>() {
+        let center = transform.position + sprite.size.as_vec2() / 2.0;
+        if seen.is_none() && fog.is_visible(tilemap.world_to_tile(center)) {
+            discovered.push(id);
+        }
+    }
+    for id in discovered {
+        let _ = world.insert_one(id, components::Discovered);
+    }
+}
+
+// spring dash crystals growing back where they were collected
+fn system_regrowth(state: &mut Level1State, dt: f32) {
+    let mut grown = vec![];
+    for (id, (transform, regrowth)) in state
+        .world
+        .query_mut::<(&components::Transform, &mut components::Regrowth)>()
     {
-        // ghost move
-        for (_id, (transform, ghost_ai)) in
-            world.query_mut::<(&mut components::Transform, &mut components::GhostAI)>()
-        {
-            let difference = target_pos - transform.position;
-            if difference.length() <= ghost_ai.radius {
-                ghost_ai.velocity = difference.normalize() * ghost_ai.speed;
-                transform.position += dt * ghost_ai.velocity;
-            }
+        regrowth.timer -= dt;
+        if regrowth.timer <= 0.0 {
+            grown.push((id, transform.position));
         }
-        // Player death
-        for (_id, (transform, sprite, _)) in &mut world.query::<(
-            &components::Transform,
-            &components::Sprite,
-            &components::GhostAI,
-        )>() {
-            if sdl2::rect::Rect::new(
-                target_pos.x as i32,
-                target_pos.y as i32,
-                target_size.x,
-                target_size.y,
+    }
+    for (id, position) in grown {
+        let _ = state.world.despawn(id);
+        create_dash_crystal_on(state, position.x as i32, position.y as i32);
+    }
+}
+
+// summer heat zones warm the player up, overheating costs a life and cools them down
+fn system_heat(
+    world: &mut hecs::World,
+    tilemap: &Tilemap,
+    particles_state: &mut sdl2_particles::ParticlesState,
+    player_death: &mut bool,
+    rng: &mut ThreadRng,
+    dt: f32,
+) {
+    let mut overheated = false;
+    for (_id, (transform, collider, heat)) in world.query_mut::<(
+        &components::Transform,
+        &components::Collider,
+        &mut components::Heat,
+    )>() {
+        let center = collider.center(transform.position);
+        heat.level = (heat.level + tilemap.properties_at(center).heat * dt).max(0.0);
+        // shimmer around the player when close to overheating
+        if heat.level > 0.5 && rng.gen_bool((heat.level * 0.5) as f64) {
+            let particle_type = sdl2_particles::ParticleTypeBuilder::new(
+                rng.gen_range(4..10),
+                rng.gen_range(4..10),
+                std::time::Duration::from_millis(rng.gen_range(300..500)),
             )
-            .has_intersection(sdl2::rect::Rect::new(
-                transform.position.x as i32,
-                transform.position.y as i32,
-                sprite.size.x,
-                sprite.size.y,
-            )) {
-                should_die = true;
-                break;
-            }
+            .with_color(sdl2::pixels::Color::RGB(
+                rng.gen_range(200..255),
+                rng.gen_range(60..120),
+                20,
+            ))
+            .with_effect(sdl2_particles::ParticleEffect::FadeOut {
+                delay: std::time::Duration::from_millis(100),
+            })
+            .with_effect(sdl2_particles::ParticleEffect::LinearMovement {
+                velocity_x: rng.gen_range(-40.0..40.0),
+                velocity_y: rng.gen_range(-160.0..-80.0),
+            })
+            .build();
+            particles_state.emit(
+                1,
+                &particle_type,
+                center.x + rng.gen_range(-20.0..20.0),
+                center.y,
+            );
+        }
+        if heat.level >= 1.0 {
+            heat.level = 0.0;
+            overheated = true;
         }
     }
-    if should_die {
-        player_damage(world, player_death);
+    if overheated {
+        println!("Player overheated!");
+        player_damage(world, 1, player_death);
     }
 }
 
-fn system_orbit_ai(state: &mut Level1State, dt: f32) {
-    let mut optional_player_position = None;
-    let mut optional_player_size = None;
-    for (_id, (transform, sprite, _)) in &mut state.world.query::<(
+// enemy positions for separation and cohesion lookups, rebuilt every frame
+fn system_broadphase(world: &hecs::World, enemy_grid: &mut SpatialGrid) {
+    puffin::profile_scope!("system_broadphase");
+    enemy_grid.clear();
+    for (id, (transform, sprite, _)) in &mut world.query::<(
         &components::Transform,
         &components::Sprite,
-        &components::Player,
+        &components::Enemy,
     )>() {
-        optional_player_position = Some(transform.position);
-        optional_player_size = Some(sprite.size);
+        enemy_grid.insert_rect(id, transform.position, sprite.size.as_vec2());
     }
-    if let (Some(target_pos), Some(_target_size)) = (optional_player_position, optional_player_size)
+}
+
+// moving colliders are rebuilt into collider_grid every frame, static ones
+// go into static_grid once when created
+fn system_collision(
+    world: &hecs::World,
+    collider_grid: &mut SpatialGrid,
+    static_grid: &SpatialGrid,
+    contacts: &mut Vec<Contact>,
+) {
+    puffin::profile_scope!("system_collision");
+    collider_grid.clear();
+    for (id, (transform, collider)) in
+        &mut world.query::<(&components::Transform, &components::Collider)>()
     {
-        // ghost move
-        for (_id, (transform, orbit_ai)) in state
-            .world
-            .query_mut::<(&mut components::Transform, &mut components::OrbitAI)>()
+        if !collider.is_static {
+            collision::insert(collider_grid, id, collider, transform.position);
+        }
+    }
+    contacts.clear();
+    collision::find_contacts(world, collider_grid, static_grid, contacts);
+}
+
+fn system_contact_damage(world: &mut hecs::World, contacts: &[Contact], player_death: &mut bool) {
+    let should_die = contacts.iter().any(|contact| {
+        world
+            .entity(contact.a)
+            .map_or(false, |entity| entity.has::<components::Player>())
+            && world
+                .entity(contact.b)
+                .map_or(false, |entity| entity.has::<components::GhostAI>())
+    });
+    if should_die {
+        player_damage(world, 1, player_death);
+    }
+}
+
+fn system_flow_field(world: &hecs::World, tilemap: &Tilemap, flow_field: &mut FlowField) {
+    for (_id, (transform, _)) in &mut world.query::<(&components::Transform, &components::Player)>()
+    {
+        flow_field.update(tilemap, tilemap.world_to_tile(transform.position));
+    }
+}
+
+fn system_terrain_damage(world: &mut hecs::World, tilemap: &Tilemap, player_death: &mut bool) {
+    let mut damage = 0;
+    for (_id, (transform, collider, _)) in &mut world.query::<(
+        &components::Transform,
+        &components::Collider,
+        &components::Player,
+    )>() {
+        damage = tilemap
+            .properties_at(collider.center(transform.position))
+            .damage;
+    }
+    if damage > 0 {
+        player_damage(world, damage, player_death);
+    }
+}
+
+fn system_behaviour_tree(state: &mut Level1State, rng: &mut ThreadRng, dt: f32) {
+    puffin::profile_scope!("system_behaviour_tree");
+    let mut optional_player = None;
+    for (_id, (transform, controller, _)) in &mut state.world.query::<(
+        &components::Transform,
+        &components::PlayerController,
+        &components::Player,
+    )>() {
+        optional_player = Some((transform.position, controller.velocity));
+    }
+    let mut bullets_to_create = vec![];
+    if let Some((player_position, player_velocity)) = optional_player {
+        for (_id, (transform, tree, faction, collider, activity, shooting)) in
+            state.world.query_mut::<(
+                &mut components::Transform,
+                &mut BehaviourTree,
+                &Faction,
+                Option<&components::Collider>,
+                Option<&components::Activity>,
+                Option<&components::ShootingEnemy>,
+            )>()
         {
-            let difference = target_pos - transform.position;
-            let x = transform.position.x;
-            let y = transform.position.y;
-            let dx = target_pos.x; // player x
-            let dy = target_pos.y; // player y
-            let r = difference.length();
-            if r >= 250.0 && r <= 350.0 {
-                orbit_ai.is_orbiting = true;
-            } else {
-                if r < 250.0 {
-                    let delta_x = x - dx;
-                    let delta_y = y - dy;
-                    orbit_ai.angle = delta_y.atan2(delta_x).to_degrees();
-                } else {
-                    orbit_ai.is_orbiting = false;
-                }
-            }
-            if orbit_ai.is_orbiting {
-                let angle = (orbit_ai.angle).to_radians();
-                orbit_ai.target_pos.x = dx + angle.cos() * 300.0;
-                orbit_ai.target_pos.y = dy + angle.sin() * 300.0;
-            }
-            if r <= orbit_ai.radius_ghosting {
-                if r >= 400.0 {
-                    orbit_ai.target_pos = target_pos;
-                }
-                orbit_ai.velocity =
-                    (orbit_ai.target_pos - transform.position).normalize() * orbit_ai.speed;
-                transform.position += dt * orbit_ai.velocity;
+            let dt = activity_dt(activity, dt);
+            if dt == 0.0 {
+                continue;
             }
-            if transform.position.distance(orbit_ai.target_pos) <= 10.0 {
-                orbit_ai.angle += orbit_ai.angular_speed * dt;
+            let player_distance = transform.position.distance(player_position);
+            let blackboard = Blackboard {
+                position: transform.position,
+                player_position,
+                player_velocity,
+                player_distance,
+                player_visible: player_distance <= SIGHT_RANGE
+                    && state
+                        .tilemap
+                        .line_of_sight(transform.position, player_position),
+                path_direction: ai::chase_direction(
+                    &state.flow_field,
+                    &state.tilemap,
+                    transform.position,
+                    player_position,
+                ),
+                projectiles: &state.projectiles,
+                projectile: shooting.map_or("bullet", |shooting| shooting.projectile),
+                dt,
+            };
+            let actions = tree.tick(&blackboard, rng);
+            ai::move_on_terrain(
+                &state.tilemap,
+                &state.obstacle_grid,
+                transform,
+                collider,
+                actions.velocity * dt,
+            );
+            for (projectile, direction) in actions.bullets {
+                bullets_to_create.push((projectile, transform.position, direction, *faction));
             }
         }
     }
+    for (projectile, position, direction, faction) in bullets_to_create {
+        create_bullet(
+            &mut state.world,
+            &state.projectiles,
+            &projectile,
+            position,
+            direction,
+            faction,
+        );
+    }
+}
+
+fn system_swept(world: &mut hecs::World) {
+    for (_id, (transform, swept)) in
+        world.query_mut::<(&components::Transform, &mut components::Swept)>()
+    {
+        swept.previous_position = transform.position;
+    }
 }
 
-fn system_animation(world: &mut hecs::World, dt: f32) {
-    for (_id, animation_state) in world.query_mut::<&mut components::Animation>() {
-        animation_state
-            .state
-            .update(std::time::Duration::from_secs_f32(dt));
+fn system_lifetime(world: &mut hecs::World, dt: f32) {
+    let mut expired = vec![];
+    for (id, lifetime) in world.query_mut::<&mut components::Lifetime>() {
+        lifetime.remaining -= dt;
+        if lifetime.remaining <= 0.0 {
+            expired.push(id);
+        }
+    }
+    for id in expired {
+        let _ = world.despawn(id);
     }
 }
 
-fn system_bullets(world: &mut hecs::World, player_death: &mut bool, mob_count: &mut u32, dt: f32) {
-    let mut optional_player_position = None;
-    let mut optional_player_size = None;
-    for (_id, (transform, sprite, _)) in &mut world.query::<(
+fn system_move_bullets(
+    world: &mut hecs::World,
+    tilemap: &Tilemap,
+    enemy_grid: &SpatialGrid,
+    dt: f32,
+) {
+    puffin::profile_scope!("system_move_bullets");
+    let mut optional_player = None;
+    for (_id, (transform, collider, faction, _)) in &mut world.query::<(
         &components::Transform,
-        &components::Sprite,
+        &components::Collider,
+        &components::Faction,
         &components::Player,
     )>() {
-        optional_player_position = Some(transform.position);
-        optional_player_size = Some(sprite.size);
+        optional_player = Some((collider.center(transform.position), *faction));
     }
-    // Update bullet position
-    for (_id, (transform, bullet)) in
-        world.query_mut::<(&mut components::Transform, &components::Bullet)>()
-    {
+    // homing bullets pick the nearest target of a hostile faction
+    let mut homing_targets = HashMap::new();
+    for (id, (transform, bullet, collider, faction)) in &mut world.query::<(
+        &components::Transform,
+        &components::Bullet,
+        &components::Collider,
+        &components::Faction,
+    )>() {
+        if bullet.behaviour.homing <= 0.0 {
+            continue;
+        }
+        let from = collider.center(transform.position);
+        let player = optional_player
+            .filter(|(position, player_faction)| {
+                faction.is_hostile(player_faction) && position.distance(from) <= HOMING_RADIUS
+            })
+            .map(|(position, _)| position);
+        let target = enemy_grid
+            .query_circle(from, HOMING_RADIUS)
+            .filter(|enemy| {
+                world
+                    .get::<&components::Faction>(enemy.id)
+                    .map_or(false, |other| faction.is_hostile(&other))
+            })
+            .map(|enemy| (enemy.min + enemy.max) / 2.0)
+            .chain(player)
+            .min_by(|a, b| a.distance(from).total_cmp(&b.distance(from)));
+        if let Some(target) = target {
+            homing_targets.insert(id, target);
+        }
+    }
+    let (map_min, map_max) = tilemap.bounds();
+    let mut bullets_to_delete = vec![];
+    for (id, (transform, bullet, collider)) in world.query_mut::<(
+        &mut components::Transform,
+        &mut components::Bullet,
+        &components::Collider,
+    )>() {
+        let from = collider.center(transform.position);
+        // no direction to turn from or towards when either is zero, angle_between gives NaN
+        let homing = homing_targets
+            .get(&id)
+            .map(|target| *target - from)
+            .filter(|offset| {
+                offset.length_squared() > f32::EPSILON
+                    && bullet.velocity.length_squared() > f32::EPSILON
+            });
+        if let Some(offset) = homing {
+            let turn = bullet.velocity.angle_between(offset);
+            let max_turn = (bullet.behaviour.homing * dt).to_radians();
+            bullet.velocity =
+                Vec2::from_angle(turn.clamp(-max_turn, max_turn)).rotate(bullet.velocity);
+        }
         transform.position += bullet.velocity * dt;
+        bullet.travelled += bullet.velocity.length() * dt;
+        let to = collider.center(transform.position);
+        // leaving the map either bounces off the edge or despawns
+        let outside = to.cmplt(map_min) | to.cmpgt(map_max);
+        if outside.any() {
+            if bullet.behaviour.ricochets == 0 {
+                bullets_to_delete.push(id);
+                continue;
+            }
+            bullet.behaviour.ricochets -= 1;
+            if outside.x {
+                bullet.velocity.x = -bullet.velocity.x;
+            }
+            if outside.y {
+                bullet.velocity.y = -bullet.velocity.y;
+            }
+            transform.position += to.clamp(map_min, map_max) - to;
+        }
+        if bullet.travelled >= bullet.range || tilemap.blocks_bullet(from, to) {
+            bullets_to_delete.push(id);
+        }
+    }
+    for id in bullets_to_delete {
+        let _ = world.despawn(id);
     }
+}
+
+fn system_bullets(
+    world: &mut hecs::World,
+    contacts: &[Contact],
+    player_death: &mut bool,
+    mob_count: &mut u32,
+) {
+    puffin::profile_scope!("system_bullets");
     let mut bullets_ids_to_kill = vec![];
     let mut enemies_ids_to_kill = vec![];
-    let mut should_die = false;
-    for (bullet_id, (transform, sprite, bullet)) in &mut world.query::<(
-        &components::Transform,
-        &components::Sprite,
-        &components::Bullet,
-    )>() {
-        let bullet_rect = sdl2::rect::Rect::new(
-            transform.position.x as i32,
-            transform.position.y as i32,
-            sprite.size.x,
-            sprite.size.y,
-        );
-        for (enemy_id, (enemy_transform, enemy_sprite, _)) in &mut world.query::<(
-            &components::Transform,
-            &components::Sprite,
-            &components::Enemy,
-        )>() {
-            let enemy_rect = sdl2::rect::Rect::new(
-                enemy_transform.position.x as i32,
-                enemy_transform.position.y as i32,
-                enemy_sprite.size.x,
-                enemy_sprite.size.y,
-            );
-            if bullet.bullet_type == BulletType::FromPlayer
-                && bullet_rect.has_intersection(enemy_rect)
-            {
-                bullets_ids_to_kill.push(bullet_id);
-                enemies_ids_to_kill.push(enemy_id);
-                break;
+    let mut reflected = vec![];
+    let mut damage = 0;
+    for contact in contacts {
+        if bullets_ids_to_kill.contains(&contact.a) || enemies_ids_to_kill.contains(&contact.b) {
+            continue;
+        }
+        let (Ok(bullet_entity), Ok(target)) = (world.entity(contact.a), world.entity(contact.b))
+        else {
+            continue;
+        };
+        // solid props stop every bullet, piercing or not
+        let terrain = target
+            .get::<&components::Collider>()
+            .map_or(false, |collider| collider.layer == collision::TERRAIN);
+        if terrain {
+            if bullet_entity.has::<components::Bullet>() {
+                bullets_ids_to_kill.push(contact.a);
             }
-            if let (Some(pos), Some(size)) = (optional_player_position, optional_player_size) {
-                if bullet.bullet_type == BulletType::FromEnemy {
-                    let player_rect =
-                        sdl2::rect::Rect::new(pos.x as i32, pos.y as i32, size.x, size.y);
-                    if bullet_rect.has_intersection(player_rect) {
-                        should_die = true;
-                        bullets_ids_to_kill.push(bullet_id);
-                    }
-                }
+            continue;
+        }
+        let (Some(mut bullet), Some(mut faction), Some(target_faction)) = (
+            bullet_entity.get::<&mut components::Bullet>(),
+            bullet_entity.get::<&mut components::Faction>(),
+            target.get::<&components::Faction>(),
+        ) else {
+            continue;
+        };
+        if !faction.is_hostile(&target_faction) || bullet.hits.contains(&contact.b) {
+            continue;
+        }
+        if let Some(player) = target.get::<&components::Player>() {
+            // dashing through a bullet sends it back at whoever fired it
+            if matches!(player.state_machine.state, player_state::State::Dashing) {
+                *faction = *target_faction;
+                bullet.velocity = -bullet.velocity;
+                bullet.travelled = 0.0;
+                bullet.hits.clear();
+                reflected.push((contact.a, faction.projectile_layer()));
+                continue;
             }
+            damage = damage.max(bullet.damage);
+        } else if target.has::<components::Enemy>() {
+            enemies_ids_to_kill.push(contact.b);
+        }
+        // one target per bullet unless it pierces
+        if bullet.behaviour.pierce > 0 {
+            bullet.behaviour.pierce -= 1;
+            bullet.hits.push(contact.b);
+        } else {
+            bullets_ids_to_kill.push(contact.a);
         }
     }
-    if should_die {
-        player_damage(world, player_death);
+    for (bullet, layer) in reflected {
+        if let Ok(collider) = world.query_one_mut::<&mut components::Collider>(bullet) {
+            collider.layer = layer;
+        }
+    }
+    if damage > 0 {
+        player_damage(world, damage, player_death);
     }
     for bullet in bullets_ids_to_kill.iter() {
         let _ = world.despawn(*bullet);
@@ -739,24 +1233,31 @@ fn system_bullets(world: &mut hecs::World, player_death: &mut bool, mob_count: &
 }
 
 fn system_player_controller(
-    world: &mut hecs::World,
-    player_state_input: &mut player_state::Input,
-    particles_state: &mut sdl2_particles::ParticlesState,
-    sound_shoot: &sdl2::mixer::Chunk,
-    camera: &Camera,
+    state: &mut Level1State,
     input_state: &InputState,
     rng: &mut ThreadRng,
     dt: f32,
 ) {
     let mut bullets_to_create = vec![];
-    for (_id, (transform, controller, player)) in world.query_mut::<(
+    for (_id, (transform, controller, player, collider)) in state.world.query_mut::<(
         &mut components::Transform,
         &mut components::PlayerController,
         &mut components::Player,
+        Option<&components::Collider>,
     )>() {
-        let friction = 50.0 * 64.0;
+        // low grip on ice makes the player slow to speed up and to stop
+        let grip = match collider {
+            Some(collider) => {
+                state
+                    .tilemap
+                    .properties_at(collider.center(transform.position))
+                    .friction
+            }
+            None => state.tilemap.properties_at(transform.position).friction,
+        };
+        let friction = 50.0 * 64.0 * grip;
         let max_vel = 12.0 * 64.0; // GREAT VALUES 64 is one tile
-        let accel = 130.0 * 64.0;
+        let accel = 130.0 * 64.0 * grip;
 
         controller.acceleration = input_state.movement * accel; // apply movement direction
         controller.velocity += dt * controller.acceleration; // apply acceleration
@@ -775,16 +1276,30 @@ fn system_player_controller(
         match player.state_machine.state {
             player_state::State::Moving => {
                 if input_state.dash {
-                    *player_state_input = player_state::Input::Dash;
+                    state.player_state_input = player_state::Input::Dash;
                 } else {
                     if input_state.movement != Vec2::ZERO {
-                        *player_state_input = player_state::Input::Move;
+                        state.player_state_input = player_state::Input::Move;
                     }
-                    transform.position += controller.velocity * dt; // apply velocity
+                    // apply velocity
+                    ai::move_on_terrain(
+                        &state.tilemap,
+                        &state.obstacle_grid,
+                        transform,
+                        collider,
+                        controller.velocity * dt,
+                    );
                 }
             }
             player_state::State::Dashing => {
-                transform.position += input_state.movement.normalize_or_zero() * dt * max_vel * 3.0;
+                let dash = input_state.movement.normalize_or_zero() * dt * max_vel * 3.0;
+                ai::move_on_terrain(
+                    &state.tilemap,
+                    &state.obstacle_grid,
+                    transform,
+                    collider,
+                    dash,
+                );
                 let particle_type = sdl2_particles::ParticleTypeBuilder::new(
                     rng.gen_range(8..16),
                     rng.gen_range(8..16),
@@ -806,7 +1321,7 @@ fn system_player_controller(
                     velocity_y: -controller.velocity.y / 2.0 + rng.gen_range(-250.0..250.0),
                 })
                 .build();
-                particles_state.emit(
+                state.particles_state.emit(
                     1,
                     &particle_type,
                     transform.position.x + 40.0,
@@ -816,28 +1331,43 @@ fn system_player_controller(
             player_state::State::Stopped => {
                 controller.velocity = Vec2::ZERO;
                 if input_state.dash {
-                    *player_state_input = player_state::Input::Dash;
+                    state.player_state_input = player_state::Input::Dash;
                 }
             }
             player_state::State::Idle => {
                 if input_state.movement != Vec2::ZERO {
-                    *player_state_input = player_state::Input::Move;
+                    state.player_state_input = player_state::Input::Move;
                 }
-                transform.position += controller.velocity * dt; // apply velocity
+                // apply velocity
+                ai::move_on_terrain(
+                    &state.tilemap,
+                    &state.obstacle_grid,
+                    transform,
+                    collider,
+                    controller.velocity * dt,
+                );
             }
         }
 
         if input_state.attack && controller.attack_timer <= 0.0 {
             let attack_cooldown = 1.0;
             controller.attack_timer = attack_cooldown;
-            let direction = ((input_state.mouse_pos + camera.position) - transform.position)
+            let direction = ((input_state.mouse_pos + state.camera.position) - transform.position)
                 .normalize_or_zero();
-            let _ = sdl2::mixer::Channel::all().play(sound_shoot, 0);
+            let _ = sdl2::mixer::Channel::all().play(&state.sound_shoot, 0);
             bullets_to_create.push((transform.position, direction));
+            state.player_shots.push(transform.position);
         }
     }
     for (pos, dir) in bullets_to_create {
-        create_bullet(world, pos, dir, BulletType::FromPlayer);
+        create_bullet(
+            &mut state.world,
+            &state.projectiles,
+            "bullet",
+            pos,
+            dir,
+            Faction::Player,
+        );
     }
 }
 
@@ -873,7 +1403,7 @@ fn create_dash_crystal_on(state: &mut Level1State, x: i32, y: i32) {
             duration: std::time::Duration::from_secs_f32(0.1),
         },
     ]);
-    state.world.spawn((
+    let crystal = state.world.spawn((
         components::Transform {
             position: Vec2::new(x as f32, y as f32),
             rotation: 0.0,
@@ -884,8 +1414,16 @@ fn create_dash_crystal_on(state: &mut Level1State, x: i32, y: i32) {
             size: UVec2::new(80, 80),
         },
         components::DashingCrystal,
+        components::Collider::pickup(),
+        components::Activity::default(),
         crystal_animation_state,
     ));
+    collision::insert(
+        &mut state.static_grid,
+        crystal,
+        &components::Collider::pickup(),
+        Vec2::new(x as f32, y as f32),
+    );
 }
 
 fn create_point_crystal_on(state: &mut Level1State, x: i32, y: i32) {
@@ -920,7 +1458,7 @@ fn create_point_crystal_on(state: &mut Level1State, x: i32, y: i32) {
             duration: std::time::Duration::from_secs_f32(0.1),
         },
     ]);
-    state.world.spawn((
+    let crystal = state.world.spawn((
         components::Transform {
             position: Vec2::new(x as f32, y as f32),
             rotation: 0.0,
@@ -931,11 +1469,42 @@ fn create_point_crystal_on(state: &mut Level1State, x: i32, y: i32) {
             size: UVec2::new(80, 80),
         },
         components::PointCrystal,
+        components::Collider::pickup(),
+        components::Activity::default(),
         crystal_animation_state,
     ));
+    collision::insert(
+        &mut state.static_grid,
+        crystal,
+        &components::Collider::pickup(),
+        Vec2::new(x as f32, y as f32),
+    );
+}
+
+fn create_prop_on(state: &mut Level1State, prop: Prop, position: Vec2) {
+    let entity = state.world.spawn((
+        components::Transform::with_position(position.x, position.y),
+        components::Sprite {
+            filename: prop.sprite,
+            size: prop.size,
+        },
+        components::Scenery { prop },
+    ));
+    // static_grid for bullets hitting it, obstacle_grid for movement
+    if let Some(collider) = prop.collider() {
+        collision::insert(&mut state.static_grid, entity, &collider, position);
+        collision::insert(&mut state.obstacle_grid, entity, &collider, position);
+        let _ = state.world.insert_one(entity, collider);
+    }
 }
 
-fn create_enemy_on(world: &mut hecs::World, x: i32, y: i32, rng: &mut ThreadRng) {
+// enemies with a behaviour tree file are driven by it, the rest by their own components
+fn create_enemy_on(
+    world: &mut hecs::World,
+    behaviours: &Behaviours,
+    spawn: &SpawnRequest,
+    rng: &mut ThreadRng,
+) -> hecs::Entity {
     let idle_animation_snake: Animation = vec![
         Keyframe {
             x: 0,
@@ -954,48 +1523,138 @@ fn create_enemy_on(world: &mut hecs::World, x: i32, y: i32, rng: &mut ThreadRng)
     ];
     let mut enemy_animation_state = components::Animation::default();
     enemy_animation_state.state.play(&idle_animation_snake);
-    if rng.gen_bool(0.67) {
-        world.spawn((
-            components::Transform::with_position(x as f32, y as f32),
-            components::Sprite {
-                filename: "res/snake.png",
-                size: UVec2::new(64, 64),
-            },
-            components::GhostAI::default(),
-            components::Enemy,
-            enemy_animation_state,
-        ));
+    let transform = components::Transform::with_position(spawn.position.x, spawn.position.y);
+    let sprite = components::Sprite {
+        filename: "res/snake.png",
+        size: UVec2::new(64, 64),
+    };
+    // lurkers hunt snakes as well and their rings hit anything around them
+    let faction = if spawn.kind == EnemyKind::Lurker {
+        Faction::Wild
     } else {
-        world.spawn((
-            components::Transform::with_position(x as f32, y as f32),
-            components::Sprite {
-                filename: "res/snake.png",
-                size: UVec2::new(64, 64),
-            },
-            components::OrbitAI::default(),
-            components::ShootingEnemy::default(),
-            components::Enemy,
+        Faction::Snakes
+    };
+    if let Some(tree) = behaviours.instantiate(spawn.kind.name(), rng) {
+        return world.spawn((
+            transform,
+            sprite,
+            tree,
+            components::Enemy { kind: spawn.kind },
+            components::Collider::enemy(),
+            faction,
+            components::Activity::default(),
             enemy_animation_state,
         ));
     }
+    match spawn.kind {
+        EnemyKind::Ghost => {
+            let mut ghost_ai = components::GhostAI::default();
+            if let Some(radius) = spawn.detection_radius {
+                ghost_ai.radius = radius;
+            }
+            let awareness = components::Awareness::with_detection_radius(ghost_ai.radius);
+            world.spawn((
+                transform,
+                sprite,
+                ghost_ai,
+                awareness,
+                components::Enemy { kind: spawn.kind },
+                components::Collider::enemy(),
+                faction,
+                components::Activity::default(),
+                enemy_animation_state,
+            ))
+        }
+        shooter => {
+            let mut orbit_ai = components::OrbitAI {
+                clockwise: rng.gen_bool(0.5),
+                // snipers strafe back and forth while firing leading shots
+                strafe: shooter == EnemyKind::Sniper,
+                ..Default::default()
+            };
+            if let Some(radius) = spawn.detection_radius {
+                orbit_ai.radius_ghosting = radius;
+            }
+            debug_assert!(
+                orbit_ai.band_inner < orbit_ai.radius_orbiting
+                    && orbit_ai.radius_orbiting < orbit_ai.band_outer,
+                "orbit band must be around the orbit radius"
+            );
+            let awareness = components::Awareness::with_detection_radius(orbit_ai.radius_ghosting);
+            world.spawn((
+                transform,
+                sprite,
+                orbit_ai,
+                awareness,
+                shooting_enemy(shooter),
+                components::Enemy { kind: spawn.kind },
+                components::Collider::enemy(),
+                faction,
+                components::Activity::default(),
+                enemy_animation_state,
+            ))
+        }
+    }
+}
+
+fn shooting_enemy(kind: EnemyKind) -> components::ShootingEnemy {
+    let (cooldown, pattern) = match kind {
+        EnemyKind::Sniper => (1.5, BulletPattern::Leading),
+        EnemyKind::Spinner => (
+            0.3,
+            BulletPattern::Spiral {
+                arms: 3,
+                step: 20.0,
+            },
+        ),
+        EnemyKind::Bloomer => (2.5, BulletPattern::Ring { count: 8 }),
+        _ => (1.0, BulletPattern::Aimed),
+    };
+    let projectile = match kind {
+        EnemyKind::Sniper => "sniper_round",
+        EnemyKind::Spinner => "bouncing_bullet",
+        EnemyKind::Bloomer => "seeker",
+        _ => "bullet",
+    };
+    components::ShootingEnemy {
+        timer: cooldown,
+        cooldown,
+        pattern,
+        projectile,
+        ..Default::default()
+    }
 }
 
 fn create_bullet(
     world: &mut hecs::World,
+    projectiles: &Projectiles,
+    name: &str,
     position: Vec2,
     direction: Vec2,
-    bullet_type: BulletType,
+    faction: Faction,
 ) {
-    let speed = 64.0 * 15.0;
+    let definition = projectiles.get(name);
     world.spawn((
         components::Transform::with_position(position.x, position.y),
         components::Sprite {
-            filename: "res/bullet.png",
-            size: UVec2::new(32, 32),
+            filename: definition.sprite,
+            size: definition.size,
+        },
+        definition.collider(faction.projectile_layer()),
+        components::Swept {
+            previous_position: position,
+        },
+        components::Lifetime {
+            remaining: definition.lifetime,
         },
+        faction,
         components::Bullet {
-            velocity: direction * speed,
-            bullet_type,
+            velocity: direction * definition.speed,
+            damage: definition.damage,
+            behaviour: definition.behaviour,
+            range: definition.range,
+            travelled: 0.0,
+            hits: vec![],
         },
     ));
 }
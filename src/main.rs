@@ -1,11 +1,29 @@
-use crate::{input::InputState, level1::Level1State, menu::{MenuState, unblock_button}, intro::IntroState};
+use crate::{input::InputState, level1::{Level1State, MapSource}, menu::{MenuState, unblock_button}, intro::IntroState, season::Season};
 
+mod activity;
+mod ai;
+mod behaviour_tree;
+mod bullet_pattern;
+mod collision;
 mod components;
+mod director;
+mod flowfield;
+mod fog;
 mod input;
+mod minimap;
 mod player_state;
+mod projectile;
+mod prop;
 mod render;
+mod season;
+mod spatial;
+mod stats;
+mod steering;
+mod stream;
 mod systems;
 mod texturemanager;
+mod tiled;
+mod worldgen;
 
 mod intro;
 mod menu;
@@ -15,7 +33,8 @@ pub enum Level {
     Intro,
     Menu,
     Level1,
-    ResetLevel1,
+    StartLevel1(Season),
+    WonLevel1,
 }
 
 pub const SCREEN_WIDTH: u32 = 1280;
@@ -60,14 +79,19 @@ fn main() {
     let mut fps_counter = 0;
     let mut fps = 0;
 
+    // optional Tiled map to play instead of a generated one, or --endless for an endless world
+    let source = match std::env::args().nth(1) {
+        Some(arg) if arg == "--endless" => MapSource::Endless,
+        Some(path) => MapSource::Tiled(path),
+        None => MapSource::Generated,
+    };
     let mut level = Level::Menu;
     let mut intro_state = IntroState::new(&mut canvas);
-    let mut level1_state = Level1State::new(&mut canvas);
+    let mut level1_state = Level1State::new(&mut canvas, Season::Autumn, source.clone());
     let mut menu_state = MenuState::new(&mut canvas);
 
     unblock_button(&mut menu_state, 0);
-    unblock_button(&mut menu_state, 1);
-    unblock_button(&mut menu_state, 3);
+    unblock_button(&mut menu_state, season::UNLOCK_ORDER[0].button());
 
     loop {
         puffin::GlobalProfiler::lock().new_frame();
@@ -106,13 +130,18 @@ fn main() {
                 level1::update(&mut level1_state, &mut canvas, dt, &input_state, &mut level);
                 level1::render(&mut level1_state, &mut canvas);
             }
-            Level::ResetLevel1 => {
-                //canvas.set_draw_color(sdl2::pixels::Color::RGB(3, 0, 52));
-                //canvas.clear();
-                level1_state = Level1State::new(&mut canvas);
-                let _ = sdl2::mixer::Channel::all().play(&sound_win, 0);
+            Level::StartLevel1(season) => {
+                level1_state = Level1State::new(&mut canvas, season, source.clone());
                 level = Level::Level1;
-                //canvas.present();
+            }
+            // beating a season unlocks the next one in the menu
+            Level::WonLevel1 => {
+                let _ = sdl2::mixer::Channel::all().play(&sound_win, 0);
+                if let Some(next) = level1_state.season.next() {
+                    unblock_button(&mut menu_state, next.button());
+                }
+                level1_state = Level1State::new(&mut canvas, level1_state.season, source.clone());
+                level = Level::Menu;
             }
         }
     }
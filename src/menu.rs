@@ -2,7 +2,7 @@ use glam::{UVec2};
 use hecs::{With, Without};
 use sdl2::{render::TextureCreator, video::WindowContext};
 
-use crate::{components, input::InputState, render::Camera, texturemanager::TextureManager, Level, systems::system_camera_follow};
+use crate::{components, input::InputState, render::Camera, season::Season, texturemanager::TextureManager, Level, systems::system_camera_follow};
 
 pub struct MenuState {
     update_started: bool,
@@ -70,13 +70,9 @@ pub fn update(state: &mut MenuState, dt: f32, input_state: &mut InputState, leve
     }
 
     if (input_state.attack || input_state.dash) && is_button_available(state.buttons_state, state.current_button) {
-        match state.current_button {
-            0 => input_state.quit = true,
-            1 => {}
-            2 => {}
-            3 => *level = Level::Level1,
-            4 => {}
-            _ => {}
+        match Season::from_button(state.current_button) {
+            Some(season) => *level = Level::StartLevel1(season),
+            None => input_state.quit = true,
         }
     }
 
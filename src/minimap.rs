@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2};
+use sdl2::{
+    image::LoadSurface,
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    surface::Surface,
+    video::{Window, WindowContext},
+};
+
+use crate::{
+    fog::Fog,
+    render::{Camera, Tilemap},
+    SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+
+// the corner map's longer side and its distance from the screen edges in px
+const CORNER_SIZE: u32 = 200;
+const CORNER_MARGIN: i32 = 16;
+const FULLSCREEN_MARGIN: i32 = 40;
+const PLAYER_COLOR: Color = Color::RGB(255, 255, 255);
+const CRYSTAL_COLOR: Color = Color::RGB(250, 210, 60);
+const ENEMY_COLOR: Color = Color::RGB(220, 40, 40);
+
+// what's marked on the map, world positions of the markers' centers
+pub struct Markers {
+    pub player: Option<Vec2>,
+    pub crystals: Vec<Vec2>,
+    pub enemies: Vec<Vec2>,
+}
+
+// the tilemap downsampled to a pixel per tile in the top right corner or over the whole
+// screen, the texture is only rebuilt when the tilemap changes or more of it is explored
+pub struct Minimap {
+    pub fullscreen: bool,
+    texture: Option<((u32, u32), Texture)>, // tilemap and fog versions it was built from
+    colors: HashMap<&'static str, Color>,   // average color of every tile image
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self {
+            fullscreen: false,
+            texture: None,
+            colors: HashMap::new(),
+        }
+    }
+    // rebakes the map texture when the tiles or the fog changed since the last bake
+    pub fn update(
+        self: &mut Self,
+        tilemap: &Tilemap,
+        fog: &Fog,
+        background: Color,
+        texture_creator: &TextureCreator<WindowContext>,
+    ) {
+        let versions = (tilemap.version(), fog.version());
+        if self
+            .texture
+            .as_ref()
+            .map_or(true, |(built, _)| *built != versions)
+        {
+            let texture = self.bake(tilemap, fog, background, texture_creator);
+            if let Some((_, old)) = self.texture.replace((versions, texture)) {
+                // textures aren't freed on drop with unsafe_textures
+                unsafe { old.destroy() };
+            }
+        }
+    }
+    pub fn render(
+        self: &Self,
+        tilemap: &Tilemap,
+        camera: &Camera,
+        markers: &Markers,
+        canvas: &mut Canvas<Window>,
+    ) {
+        puffin::profile_scope!("render_minimap");
+        let rect = self.rect(tilemap);
+        canvas.set_blend_mode(BlendMode::Blend);
+        if self.fullscreen {
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 160));
+            let _ = canvas.fill_rect(None);
+        }
+        if let Some((_, texture)) = &self.texture {
+            let _ = canvas.copy(texture, None, rect);
+        }
+        let (min, max) = tilemap.bounds();
+        let to_map = |position: Vec2| {
+            let relative = (position - min) / (max - min);
+            return (
+                rect.x() + (relative.x * rect.width() as f32) as i32,
+                rect.y() + (relative.y * rect.height() as f32) as i32,
+            );
+        };
+        // camera view
+        let (x, y) = to_map(camera.position);
+        let (right, bottom) =
+            to_map(camera.position + Vec2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32));
+        let view = Rect::new(x, y, (right - x).max(1) as u32, (bottom - y).max(1) as u32);
+        if let Some(view) = view.intersection(rect) {
+            canvas.set_draw_color(Color::RGBA(255, 255, 255, 160));
+            let _ = canvas.draw_rect(view);
+        }
+        let size = if self.fullscreen { 6 } else { 4 };
+        let mut mark = |position: Vec2, color: Color| {
+            let (x, y) = to_map(position);
+            let marker = Rect::from_center((x, y), size, size);
+            if rect.has_intersection(marker) {
+                canvas.set_draw_color(color);
+                let _ = canvas.fill_rect(marker);
+            }
+        };
+        for enemy in markers.enemies.iter() {
+            mark(*enemy, ENEMY_COLOR);
+        }
+        for crystal in markers.crystals.iter() {
+            mark(*crystal, CRYSTAL_COLOR);
+        }
+        if let Some(player) = markers.player {
+            mark(player, PLAYER_COLOR);
+        }
+        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        let _ = canvas.draw_rect(rect);
+    }
+    // where the map goes on screen, scaled to fit and keeping its aspect ratio
+    fn rect(self: &Self, tilemap: &Tilemap) -> Rect {
+        let (min, max) = tilemap.bounds();
+        let size = max - min;
+        let space = if self.fullscreen {
+            Vec2::new(
+                (SCREEN_WIDTH as i32 - 2 * FULLSCREEN_MARGIN) as f32,
+                (SCREEN_HEIGHT as i32 - 2 * FULLSCREEN_MARGIN) as f32,
+            )
+        } else {
+            Vec2::splat(CORNER_SIZE as f32)
+        };
+        let scaled = size * (space / size).min_element();
+        let (width, height) = (scaled.x.max(1.0) as u32, scaled.y.max(1.0) as u32);
+        if self.fullscreen {
+            return Rect::from_center(
+                (SCREEN_WIDTH as i32 / 2, SCREEN_HEIGHT as i32 / 2),
+                width,
+                height,
+            );
+        }
+        return Rect::new(
+            SCREEN_WIDTH as i32 - CORNER_MARGIN - width as i32,
+            CORNER_MARGIN,
+            width,
+            height,
+        );
+    }
+    fn bake(
+        self: &mut Self,
+        tilemap: &Tilemap,
+        fog: &Fog,
+        background: Color,
+        texture_creator: &TextureCreator<WindowContext>,
+    ) -> Texture {
+        let (width, height) = (tilemap.width(), tilemap.height());
+        let mut pixels = vec![0; width * height * 3];
+        for tile in tilemap.region(IVec2::ZERO, IVec2::new(width as i32, height as i32) - 1) {
+            let color = match tilemap.get(tile) {
+                // nothing is known about unexplored tiles
+                _ if !fog.is_explored(tile) => Color::RGB(0, 0, 0),
+                Some(tile) => self.color(tile.filename),
+                // empty tiles are water, drawn like the background under the map
+                None => background,
+            };
+            let index = (tile.y as usize * width + tile.x as usize) * 3;
+            pixels[index..index + 3].copy_from_slice(&[color.r, color.g, color.b]);
+        }
+        let mut texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .unwrap();
+        let _ = texture.update(None, &pixels, width * 3);
+        return texture;
+    }
+    fn color(self: &mut Self, filename: &'static str) -> Color {
+        return *self
+            .colors
+            .entry(filename)
+            .or_insert_with(|| average_color(filename));
+    }
+}
+
+// goes with the level state, a new one bakes its own texture
+impl Drop for Minimap {
+    fn drop(self: &mut Self) {
+        if let Some((_, texture)) = self.texture.take() {
+            // textures aren't freed on drop with unsafe_textures
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+// alpha weighted average of the image's pixels, grey when it can't be read
+fn average_color(filename: &str) -> Color {
+    let surface = Surface::from_file(filename)
+        .and_then(|surface| surface.convert_format(PixelFormatEnum::RGBA32));
+    let Ok(surface) = surface else {
+        return Color::RGB(128, 128, 128);
+    };
+    let (width, pitch) = (surface.width() as usize, surface.pitch() as usize);
+    let mut sum = [0u64; 4];
+    surface.with_lock(|pixels| {
+        for row in pixels.chunks(pitch) {
+            for pixel in row[..width * 4].chunks(4) {
+                let alpha = pixel[3] as u64;
+                for channel in 0..3 {
+                    sum[channel] += pixel[channel] as u64 * alpha;
+                }
+                sum[3] += alpha;
+            }
+        }
+    });
+    if sum[3] == 0 {
+        return Color::RGB(128, 128, 128);
+    }
+    return Color::RGB(
+        (sum[0] / sum[3]) as u8,
+        (sum[1] / sum[3]) as u8,
+        (sum[2] / sum[3]) as u8,
+    );
+}
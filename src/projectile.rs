@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use glam::UVec2;
+
+use crate::{
+    collision::{self, Shape},
+    components::Collider,
+};
+
+#[derive(Clone, Copy, Default)]
+pub struct BulletBehaviour {
+    pub ricochets: u32, // bounces off map edges left, leaving the map despawns otherwise
+    pub pierce: u32,    // enemies it passes through before stopping
+    pub homing: f32,    // turn rate toward the nearest target, deg per sec
+}
+
+// everything needed to spawn a projectile, who it hurts comes from the shooter's faction
+pub struct ProjectileDefinition {
+    pub sprite: &'static str,
+    pub size: UVec2,
+    pub speed: f32,
+    pub damage: u8,
+    pub lifetime: f32,
+    pub range: f32,
+    pub radius: f32, // circle collider centered on the sprite
+    pub behaviour: BulletBehaviour,
+}
+
+impl ProjectileDefinition {
+    pub fn collider(self: &Self, layer: u32) -> Collider {
+        Collider {
+            shape: Shape::Circle {
+                radius: self.radius,
+            },
+            offset: self.size.as_vec2() / 2.0,
+            layer,
+            // factions decide who actually gets hurt
+            mask: collision::PLAYER | collision::ENEMY | collision::TERRAIN,
+            is_static: false,
+        }
+    }
+}
+
+impl Default for ProjectileDefinition {
+    fn default() -> Self {
+        Self {
+            sprite: "res/bullet.png",
+            size: UVec2::new(32, 32),
+            speed: 64.0 * 15.0,
+            damage: 1,
+            lifetime: 4.0,
+            range: 64.0 * 32.0,
+            radius: 8.0,
+            behaviour: BulletBehaviour::default(),
+        }
+    }
+}
+
+pub struct Projectiles {
+    definitions: HashMap<&'static str, ProjectileDefinition>,
+}
+
+impl Projectiles {
+    pub fn new() -> Self {
+        let mut definitions = HashMap::new();
+        definitions.insert("bullet", ProjectileDefinition::default());
+        // spirals fill the arena by bouncing off its edges
+        definitions.insert(
+            "bouncing_bullet",
+            ProjectileDefinition {
+                behaviour: BulletBehaviour {
+                    ricochets: 2,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        // slow ring that bends toward its target
+        definitions.insert(
+            "seeker",
+            ProjectileDefinition {
+                speed: 64.0 * 8.0,
+                lifetime: 6.0,
+                behaviour: BulletBehaviour {
+                    homing: 45.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        // sniper rounds travel further and go through the first body
+        definitions.insert(
+            "sniper_round",
+            ProjectileDefinition {
+                speed: 64.0 * 20.0,
+                range: 64.0 * 48.0,
+                radius: 6.0,
+                behaviour: BulletBehaviour {
+                    pierce: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        Self { definitions }
+    }
+    // unknown names fall back to the plain bullet
+    pub fn get(self: &Self, name: &str) -> &ProjectileDefinition {
+        self.definitions
+            .get(name)
+            .unwrap_or_else(|| &self.definitions["bullet"])
+    }
+    pub fn speed(self: &Self, name: &str) -> f32 {
+        self.get(name).speed
+    }
+}
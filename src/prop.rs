@@ -0,0 +1,89 @@
+use glam::{UVec2, Vec2};
+
+use crate::{
+    collision::{self, Shape},
+    components::Collider,
+};
+
+// scenery placed by worldgen or as "prop" objects in Tiled maps
+#[derive(Clone, Copy)]
+pub struct Prop {
+    pub name: &'static str,
+    pub sprite: &'static str,
+    pub size: UVec2,
+    pub solid: Option<Vec2>, // box at the bottom middle of the sprite blocking movement and bullets
+    pub flat: bool,          // lies on the ground under everything else instead of being y-sorted
+}
+
+pub const TREE: Prop = Prop {
+    name: "tree",
+    sprite: "res/tree.png",
+    size: UVec2::new(96, 128),
+    solid: Some(Vec2::new(24.0, 20.0)),
+    flat: false,
+};
+pub const PINE: Prop = Prop {
+    name: "pine",
+    sprite: "res/pine.png",
+    size: UVec2::new(80, 128),
+    solid: Some(Vec2::new(20.0, 20.0)),
+    flat: false,
+};
+pub const CACTUS: Prop = Prop {
+    name: "cactus",
+    sprite: "res/cactus.png",
+    size: UVec2::new(48, 80),
+    solid: Some(Vec2::new(24.0, 16.0)),
+    flat: false,
+};
+pub const ROCK: Prop = Prop {
+    name: "rock",
+    sprite: "res/rock.png",
+    size: UVec2::new(64, 48),
+    solid: Some(Vec2::new(52.0, 28.0)),
+    flat: false,
+};
+// bushes hide the player without stopping anything
+pub const BUSH: Prop = Prop {
+    name: "bush",
+    sprite: "res/bush.png",
+    size: UVec2::new(64, 48),
+    solid: None,
+    flat: false,
+};
+pub const LEAVES: Prop = Prop {
+    name: "leaves",
+    sprite: "res/leaves.png",
+    size: UVec2::new(64, 64),
+    solid: None,
+    flat: true,
+};
+pub const FLOWERS: Prop = Prop {
+    name: "flowers",
+    sprite: "res/flowers.png",
+    size: UVec2::new(64, 64),
+    solid: None,
+    flat: true,
+};
+pub const PROPS: [Prop; 7] = [TREE, PINE, CACTUS, ROCK, BUSH, LEAVES, FLOWERS];
+
+pub fn find(name: &str) -> Option<Prop> {
+    return PROPS.into_iter().find(|prop| prop.name == name);
+}
+
+impl Prop {
+    // where it stands, the bottom middle of the sprite drawn at `position`
+    pub fn foot(self: &Self, position: Vec2) -> Vec2 {
+        return position + Vec2::new(self.size.x as f32 / 2.0, self.size.y as f32);
+    }
+    pub fn collider(self: &Self) -> Option<Collider> {
+        let size = self.solid?;
+        return Some(Collider {
+            shape: Shape::Aabb { size },
+            offset: self.foot(Vec2::ZERO) - Vec2::new(0.0, size.y / 2.0),
+            layer: collision::TERRAIN,
+            mask: 0,
+            is_static: true,
+        });
+    }
+}
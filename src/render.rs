@@ -1,4 +1,14 @@
+use std::collections::HashMap;
+
 use glam::{IVec2, Vec2};
+use sdl2::{
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    render::{BlendMode, Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+};
+
+use crate::{texturemanager::TextureManager, SCREEN_HEIGHT, SCREEN_WIDTH};
 
 pub struct Camera {
     pub position: Vec2,
@@ -18,47 +28,921 @@ impl Camera {
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct TileProperties {
+    pub walkable: bool,
+    pub blocks_bullets: bool, // also blocks line of sight
+    pub speed_multiplier: f32,
+    pub damage: u8,    // lives lost on contact, invincibility frames apply
+    pub friction: f32, // scales the player's grip, ice is low
+    pub heat: f32,     // heat gained per second in summer, negative cools down
+}
+
+// how a tile picks its look from the neighbouring tiles of the same type
+#[derive(Clone, Copy)]
+pub enum Autotile {
+    None,
+    // 4x4 atlas indexed by the 4-bit mask of edge neighbours (N 1, E 2, S 4, W 8)
+    Edges(&'static str),
+    // 8x6 atlas of the 47 blob variants, corners only count when both edges next
+    // to them connect so inner corners get their own notch
+    Blob(&'static str),
+}
+
+#[derive(Clone, Copy)]
 pub struct Tile {
     pub filename: &'static str,
+    pub autotile: Autotile,
+    pub properties: TileProperties,
+}
+
+pub const PATH: Tile = Tile {
+    filename: "res/path.png",
+    autotile: Autotile::Edges("res/path_edges.png"),
+    properties: TileProperties {
+        walkable: true,
+        blocks_bullets: false,
+        speed_multiplier: 1.2,
+        damage: 0,
+        friction: 1.0,
+        heat: 0.0,
+    },
+};
+pub const GRASS: Tile = Tile {
+    filename: "res/grass.png",
+    autotile: Autotile::Blob("res/grass_blob.png"),
+    properties: TileProperties {
+        walkable: true,
+        blocks_bullets: false,
+        speed_multiplier: 1.0,
+        damage: 0,
+        friction: 1.0,
+        heat: 0.0,
+    },
+};
+// spring
+pub const SOIL: Tile = Tile {
+    filename: "res/soil.png",
+    autotile: Autotile::Edges("res/soil_edges.png"),
+    properties: PATH.properties,
+};
+pub const MEADOW: Tile = Tile {
+    filename: "res/meadow.png",
+    autotile: Autotile::Blob("res/meadow_blob.png"),
+    properties: GRASS.properties,
+};
+// summer, sand paths are the heat zones and grass gives some shade
+pub const SAND: Tile = Tile {
+    filename: "res/sand.png",
+    autotile: Autotile::Edges("res/sand_edges.png"),
+    properties: TileProperties {
+        speed_multiplier: 1.1,
+        heat: 0.25,
+        ..PATH.properties
+    },
+};
+pub const DRY_GRASS: Tile = Tile {
+    filename: "res/dry_grass.png",
+    autotile: Autotile::Blob("res/dry_grass_blob.png"),
+    properties: TileProperties {
+        heat: -0.1,
+        ..GRASS.properties
+    },
+};
+// winter
+pub const ICE: Tile = Tile {
+    filename: "res/ice.png",
+    autotile: Autotile::Edges("res/ice_edges.png"),
+    properties: TileProperties {
+        speed_multiplier: 1.3,
+        friction: 0.15,
+        ..PATH.properties
+    },
+};
+pub const SNOW: Tile = Tile {
+    filename: "res/snow.png",
+    autotile: Autotile::Blob("res/snow_blob.png"),
+    properties: TileProperties {
+        speed_multiplier: 0.8,
+        ..GRASS.properties
+    },
+};
+// rock faces, nothing walks, shoots or looks through them
+pub const CLIFF: Tile = Tile {
+    filename: "res/cliff.png",
+    autotile: Autotile::Blob("res/cliff_blob.png"),
+    properties: TileProperties {
+        walkable: false,
+        blocks_bullets: true,
+        speed_multiplier: 1.0,
+        damage: 0,
+        friction: 1.0,
+        heat: 0.0,
+    },
+};
+// hurts on contact and slows the way out
+pub const THORNS: Tile = Tile {
+    filename: "res/thorns.png",
+    autotile: Autotile::Edges("res/thorns_edges.png"),
+    properties: TileProperties {
+        speed_multiplier: 0.6,
+        damage: 1,
+        ..GRASS.properties
+    },
+};
+// every tile a map can use
+pub const TILES: [Tile; 10] = [
+    PATH, GRASS, SOIL, MEADOW, SAND, DRY_GRASS, ICE, SNOW, CLIFF, THORNS,
+];
+// empty tiles are water
+pub const WATER: TileProperties = TileProperties {
+    walkable: true,
+    blocks_bullets: false,
+    speed_multiplier: 0.4,
+    damage: 0,
+    friction: 1.0,
+    heat: -0.5,
+};
+// outside of the map
+pub const VOID: TileProperties = TileProperties {
+    walkable: false,
+    blocks_bullets: false,
+    speed_multiplier: 1.0,
+    damage: 0,
+    friction: 1.0,
+    heat: 0.0,
+};
+
+// tiles per chunk side, chunks are the unit of storage and of cached rendering
+pub const CHUNK_SIZE: usize = 8;
+
+// square pixels of float error tolerated when a stuck box steps without going deeper
+const OVERLAP_EPSILON: f32 = 0.01;
+
+// index into Tilemap's tile table shifted by one, 0 is an empty (water) tile
+pub type TileId = u8;
+
+struct Chunk {
+    tiles: [TileId; CHUNK_SIZE * CHUNK_SIZE],
+    variants: [u8; CHUNK_SIZE * CHUNK_SIZE], // atlas cell picked by the autotiler
+    version: u32, // bumped on every change so cached textures know to redraw
+}
+
+impl Chunk {
+    fn empty() -> Self {
+        return Self {
+            tiles: [0; CHUNK_SIZE * CHUNK_SIZE],
+            variants: [0; CHUNK_SIZE * CHUNK_SIZE],
+            version: 0,
+        };
+    }
+}
+
+// neighbour offsets and their mask bits, clockwise from north
+const NEIGHBOUR_BITS: [(IVec2, u8); 8] = [
+    (IVec2::new(0, -1), 1),
+    (IVec2::new(1, -1), 2),
+    (IVec2::new(1, 0), 4),
+    (IVec2::new(1, 1), 8),
+    (IVec2::new(0, 1), 16),
+    (IVec2::new(-1, 1), 32),
+    (IVec2::new(-1, 0), 64),
+    (IVec2::new(-1, -1), 128),
+];
+
+// blob atlas cell of every 8-bit mask, cells are the reduced masks in ascending order
+const BLOB_CELLS: [u8; 256] = blob_cells();
+
+const fn reduce_blob_mask(mask: u8) -> u8 {
+    let mut reduced = mask & (1 | 4 | 16 | 64);
+    let mut corner = 0;
+    while corner < 4 {
+        let diagonal = 2 << (corner * 2);
+        let before = 1 << (corner * 2);
+        let after = (1u16 << ((corner * 2 + 2) % 8)) as u8;
+        if mask & diagonal != 0 && mask & before != 0 && mask & after != 0 {
+            reduced |= diagonal;
+        }
+        corner += 1;
+    }
+    reduced
+}
+
+const fn blob_cells() -> [u8; 256] {
+    let mut cells = [0; 256];
+    let mut cell_of_reduced = [0; 256];
+    let mut next = 0;
+    let mut mask = 0;
+    while mask < 256 {
+        if reduce_blob_mask(mask as u8) == mask as u8 {
+            cell_of_reduced[mask] = next;
+            next += 1;
+        }
+        mask += 1;
+    }
+    let mut mask = 0;
+    while mask < 256 {
+        cells[mask] = cell_of_reduced[reduce_blob_mask(mask as u8) as usize];
+        mask += 1;
+    }
+    cells
 }
 
 pub struct Tilemap {
-    pub values: Vec<Vec<Option<Tile>>>,
+    tiles: Vec<Tile>,
+    chunks: Vec<Chunk>,
+    chunks_y: usize,
+    width: usize,
+    height: usize,
     pub tile_width: u32,
     pub tile_height: u32,
     tilemap_width: u32,
     tilemap_height: u32,
+    position: IVec2, // world position of the top left corner, centered until scrolled
+    version: u32,    // bumped on every tile change and scroll, for caches of the whole map
 }
 
 impl Tilemap {
     pub fn new(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Self {
-        let mut values = vec![];
-        for _x in 0..width {
-            let mut row = vec![];
-            for _y in 0..height {
-                row.push(None);
-            }
-            values.push(row);
+        let chunks_x = (width as usize).div_ceil(CHUNK_SIZE);
+        let chunks_y = (height as usize).div_ceil(CHUNK_SIZE);
+        let mut chunks = vec![];
+        for _ in 0..chunks_x * chunks_y {
+            chunks.push(Chunk::empty());
         }
         Self {
-            values,
+            tiles: vec![],
+            chunks,
+            chunks_y,
+            width: width as usize,
+            height: height as usize,
             tile_width,
             tile_height,
             tilemap_height: height * tile_height,
-            tilemap_width: width * tile_width, 
+            tilemap_width: width * tile_width,
+            position: IVec2::new(
+                -((width * tile_width) as i32 / 2),
+                -((height * tile_height) as i32 / 2),
+            ),
+            version: 0,
         }
     }
-    pub fn get(self: &Self, x: usize, y: usize) -> Option<&Tile> {
-        return self.values.get(x).unwrap().get(y).unwrap().as_ref();
+    // None for empty tiles and outside of the map
+    pub fn get(self: &Self, tile: IVec2) -> Option<&Tile> {
+        return self.try_get(tile).flatten();
     }
-
-    pub fn set(self: &mut Self, x: usize, y: usize, value: Option<Tile>) {
-        self.values.get_mut(x).unwrap().insert(y, value);
+    // None outside of the map, Some(None) for empty tiles
+    pub fn try_get(self: &Self, tile: IVec2) -> Option<Option<&Tile>> {
+        return self.id(tile).map(|id| self.tile(id));
     }
-    pub fn position(self: &Self) -> IVec2 {
+    // replaces the tile, returns the previous id or None when outside of the map
+    pub fn set(self: &mut Self, tile: IVec2, value: Option<Tile>) -> Option<TileId> {
+        self.locate(tile)?;
+        let id = value.map_or(0, |tile| self.register(tile));
+        return self.write(tile, id);
+    }
+    // every write goes through here so neighbours get their variants recomputed
+    fn write(self: &mut Self, tile: IVec2, id: TileId) -> Option<TileId> {
+        let (chunk, index) = self.locate(tile)?;
+        let previous = self.chunks[chunk].tiles[index];
+        if previous != id {
+            self.chunks[chunk].tiles[index] = id;
+            self.chunks[chunk].version += 1;
+            self.version += 1;
+            self.autotile(tile);
+            for (offset, _) in NEIGHBOUR_BITS {
+                self.autotile(tile + offset);
+            }
+        }
+        return Some(previous);
+    }
+    // which neighbours hold the same tile, the map edge counts as connected
+    fn neighbour_mask(self: &Self, tile: IVec2, id: TileId) -> u8 {
+        let mut mask = 0;
+        for (offset, bit) in NEIGHBOUR_BITS {
+            if self.id(tile + offset).map_or(true, |other| other == id) {
+                mask |= bit;
+            }
+        }
+        return mask;
+    }
+    fn autotile(self: &mut Self, tile: IVec2) {
+        let Some((chunk, index)) = self.locate(tile) else {
+            return;
+        };
+        let id = self.chunks[chunk].tiles[index];
+        let variant = match self.tile(id).map(|tile| tile.autotile) {
+            Some(Autotile::Edges(_)) => {
+                let mask = self.neighbour_mask(tile, id);
+                // pack N E S W into the low four bits
+                (mask & 1) | (mask >> 1 & 2) | (mask >> 2 & 4) | (mask >> 3 & 8)
+            }
+            Some(Autotile::Blob(_)) => BLOB_CELLS[self.neighbour_mask(tile, id) as usize],
+            _ => 0,
+        };
+        if self.chunks[chunk].variants[index] != variant {
+            self.chunks[chunk].variants[index] = variant;
+            self.chunks[chunk].version += 1;
+        }
+    }
+    pub fn variant(self: &Self, tile: IVec2) -> Option<u8> {
+        let (chunk, index) = self.locate(tile)?;
+        return Some(self.chunks[chunk].variants[index]);
+    }
+    pub fn id(self: &Self, tile: IVec2) -> Option<TileId> {
+        let (chunk, index) = self.locate(tile)?;
+        return Some(self.chunks[chunk].tiles[index]);
+    }
+    pub fn tile(self: &Self, id: TileId) -> Option<&Tile> {
+        return id.checked_sub(1).map(|index| &self.tiles[index as usize]);
+    }
+    // registered tile types, the type with id n is at n - 1
+    pub fn tiles(self: &Self) -> &[Tile] {
+        return &self.tiles;
+    }
+    // id of the tile type, added to the table the first time it's seen
+    pub fn register(self: &mut Self, tile: Tile) -> TileId {
+        if let Some(index) = self
+            .tiles
+            .iter()
+            .position(|other| other.filename == tile.filename)
+        {
+            return index as TileId + 1;
+        }
+        self.tiles.push(tile);
+        return self.tiles.len() as TileId;
+    }
+    pub fn contains(self: &Self, tile: IVec2) -> bool {
+        return tile.x >= 0
+            && tile.y >= 0
+            && (tile.x as usize) < self.width
+            && (tile.y as usize) < self.height;
+    }
+    fn locate(self: &Self, tile: IVec2) -> Option<(usize, usize)> {
+        if !self.contains(tile) {
+            return None;
+        }
+        let (x, y) = (tile.x as usize, tile.y as usize);
+        let chunk = (x / CHUNK_SIZE) * self.chunks_y + y / CHUNK_SIZE;
+        let index = (x % CHUNK_SIZE) * CHUNK_SIZE + y % CHUNK_SIZE;
+        return Some((chunk, index));
+    }
+    // the 4 edge sharing neighbours inside the map
+    pub fn neighbours(self: &Self, tile: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y]
+            .into_iter()
+            .map(move |offset| tile + offset)
+            .filter(|neighbour| self.contains(*neighbour))
+    }
+    // tiles of the inclusive rect (min, max) inside the map
+    pub fn region(self: &Self, min: IVec2, max: IVec2) -> impl Iterator<Item = IVec2> {
+        let min = min.max(IVec2::ZERO);
+        let max = max.min(IVec2::new(self.width as i32 - 1, self.height as i32 - 1));
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+    }
+    pub fn width(self: &Self) -> usize {
+        return self.width;
+    }
+    pub fn height(self: &Self) -> usize {
+        return self.height;
+    }
+    // tiles (min, max) inclusive overlapping the screen, clamped to the map
+    pub fn visible_range(self: &Self, camera: &Camera) -> (IVec2, IVec2) {
+        let min = self.world_to_tile(camera.position).max(IVec2::ZERO);
+        let max = self
+            .world_to_tile(camera.position + Vec2::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32))
+            .min(IVec2::new(self.width as i32 - 1, self.height as i32 - 1));
+        return (min, max);
+    }
+    // chunk coordinates overlapping the screen
+    pub fn visible_chunks(self: &Self, camera: &Camera) -> impl Iterator<Item = IVec2> {
+        let (min, max) = self.visible_range(camera);
+        let (min, max) = (min / CHUNK_SIZE as i32, max / CHUNK_SIZE as i32);
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+    }
+    fn chunk_version(self: &Self, chunk: IVec2) -> u32 {
+        return self.chunks[chunk.x as usize * self.chunks_y + chunk.y as usize].version;
+    }
+    // tile containing the position, may be outside of the map
+    pub fn world_to_tile(self: &Self, position: Vec2) -> IVec2 {
+        let local = position - self.position().as_vec2();
         return IVec2::new(
-            -(self.tilemap_width as i32 / 2),
-            -(self.tilemap_height as i32 / 2),
+            (local.x / self.tile_width as f32).floor() as i32,
+            (local.y / self.tile_height as f32).floor() as i32,
+        );
+    }
+    // top left corner of the tile
+    pub fn tile_to_world(self: &Self, tile: IVec2) -> Vec2 {
+        return (self.position()
+            + tile * IVec2::new(self.tile_width as i32, self.tile_height as i32))
+        .as_vec2();
+    }
+    pub fn tile_rect(self: &Self, tile: IVec2) -> Rect {
+        let position = self.tile_to_world(tile);
+        return Rect::new(
+            position.x as i32,
+            position.y as i32,
+            self.tile_width,
+            self.tile_height,
+        );
+    }
+    pub fn properties(self: &Self, tile: IVec2) -> TileProperties {
+        return match self.try_get(tile) {
+            None => VOID,
+            Some(None) => WATER,
+            Some(Some(tile)) => tile.properties,
+        };
+    }
+    pub fn properties_at(self: &Self, position: Vec2) -> TileProperties {
+        return self.properties(self.world_to_tile(position));
+    }
+    // delta a box (min, max) can move by without entering unwalkable tiles or boxes
+    // `blocked` reports, axes are resolved separately so it slides along walls, long
+    // moves (dashing) go in half tile steps so they can't skip over a wall. A box
+    // already in unwalkable tiles may step as long as it doesn't go deeper, like
+    // overlapped props in move_on_terrain, so it isn't stuck there
+    pub fn slide(
+        self: &Self,
+        min: Vec2,
+        max: Vec2,
+        delta: Vec2,
+        blocked: impl Fn(Vec2, Vec2) -> bool,
+    ) -> Vec2 {
+        let step_length = self.tile_width.min(self.tile_height) as f32 / 2.0;
+        let steps = (delta.abs().max_element() / step_length).ceil().max(1.0);
+        let mut allowed = Vec2::ZERO;
+        for _ in 0..steps as usize {
+            for axis in [Vec2::X, Vec2::Y] {
+                let step = delta * axis / steps;
+                if step == Vec2::ZERO {
+                    continue;
+                }
+                let (next_min, next_max) = (min + allowed + step, max + allowed + step);
+                let walkable = self.is_walkable(next_min, next_max) || {
+                    let overlap = self.unwalkable_area(min + allowed, max + allowed);
+                    overlap > 0.0
+                        && self.unwalkable_area(next_min, next_max) <= overlap + OVERLAP_EPSILON
+                };
+                if walkable && !blocked(next_min, next_max) {
+                    allowed += step;
+                }
+            }
+        }
+        return allowed;
+    }
+    fn is_walkable(self: &Self, min: Vec2, max: Vec2) -> bool {
+        let min = self.world_to_tile(min);
+        let max = self.world_to_tile(max);
+        // region is clamped to the map so leaving it is checked separately
+        return self.contains(min)
+            && self.contains(max)
+            && self
+                .region(min, max)
+                .all(|tile| self.properties(tile).walkable);
+    }
+    // area of the box inside unwalkable tiles, outside the map counts as unwalkable
+    fn unwalkable_area(self: &Self, min: Vec2, max: Vec2) -> f32 {
+        let (first, last) = (self.world_to_tile(min), self.world_to_tile(max));
+        let mut area = 0.0;
+        for x in first.x..=last.x {
+            for y in first.y..=last.y {
+                let tile = IVec2::new(x, y);
+                if self.properties(tile).walkable {
+                    continue;
+                }
+                let tile_min = self.tile_to_world(tile);
+                let tile_max = self.tile_to_world(tile + IVec2::ONE);
+                let overlap = (max.min(tile_max) - min.max(tile_min)).max(Vec2::ZERO);
+                area += overlap.x * overlap.y;
+            }
+        }
+        return area;
+    }
+    // walks the segment in half tile steps, tiles blocking bullets and
+    // leaving the map break it
+    pub fn line_of_sight(self: &Self, from: Vec2, to: Vec2) -> bool {
+        return self
+            .segment_tiles(from, to)
+            .all(|tile| self.contains(tile) && !self.properties(tile).blocks_bullets);
+    }
+    // whether something stops a bullet travelling from `from` to `to` within one frame
+    pub fn blocks_bullet(self: &Self, from: Vec2, to: Vec2) -> bool {
+        return self
+            .segment_tiles(from, to)
+            .any(|tile| self.properties(tile).blocks_bullets);
+    }
+    fn segment_tiles(self: &Self, from: Vec2, to: Vec2) -> impl Iterator<Item = IVec2> + '_ {
+        let step = self.tile_width.min(self.tile_height) as f32 / 2.0;
+        let samples = (from.distance(to) / step).ceil() as usize;
+        return (0..=samples).map(move |i| {
+            let t = if samples == 0 {
+                0.0
+            } else {
+                i as f32 / samples as f32
+            };
+            self.world_to_tile(from.lerp(to, t))
+        });
+    }
+    // world space (min, max) covered by the map
+    pub fn bounds(self: &Self) -> (Vec2, Vec2) {
+        let min = self.position().as_vec2();
+        return (
+            min,
+            min + Vec2::new(self.tilemap_width as f32, self.tilemap_height as f32),
+        );
+    }
+    pub fn position(self: &Self) -> IVec2 {
+        return self.position;
+    }
+    // size of a chunk in px
+    pub fn chunk_size(self: &Self) -> IVec2 {
+        return IVec2::new(self.tile_width as i32, self.tile_height as i32) * CHUNK_SIZE as i32;
+    }
+    // moves the map over the world by whole chunks, chunks still covered keep their tiles
+    // and the ones scrolled in start empty, streamed worlds keep a fixed size map around
+    // the player this way
+    pub fn scroll(self: &mut Self, offset: IVec2) {
+        let chunks_x = self.chunks.len() / self.chunks_y;
+        let mut old: Vec<Option<Chunk>> = self.chunks.drain(..).map(Some).collect();
+        for x in 0..chunks_x as i32 {
+            for y in 0..self.chunks_y as i32 {
+                let from = IVec2::new(x, y) + offset;
+                let kept = if from.x >= 0
+                    && from.y >= 0
+                    && (from.x as usize) < chunks_x
+                    && (from.y as usize) < self.chunks_y
+                {
+                    old[from.x as usize * self.chunks_y + from.y as usize].take()
+                } else {
+                    None
+                };
+                self.chunks.push(kept.unwrap_or_else(Chunk::empty));
+            }
+        }
+        self.position += offset * self.chunk_size();
+        self.version += 1;
+    }
+    pub fn version(self: &Self) -> u32 {
+        return self.version;
+    }
+    // recomputes the variants of the inclusive rect, tiles along chunks scrolled in
+    // were autotiled against the map edge
+    pub fn retile(self: &mut Self, min: IVec2, max: IVec2) {
+        let region: Vec<IVec2> = self.region(min, max).collect();
+        for tile in region {
+            self.autotile(tile);
+        }
+    }
+    // chunk coordinate that doesn't change when the map scrolls
+    fn chunk_key(self: &Self, chunk: IVec2) -> IVec2 {
+        let chunk_size = self.chunk_size();
+        return chunk
+            + IVec2::new(
+                self.position.x.div_euclid(chunk_size.x),
+                self.position.y.div_euclid(chunk_size.y),
+            );
+    }
+}
+
+// static chunks pre-rendered into textures so drawing the map costs one copy per
+// visible chunk, textures of chunks far off screen are freed
+pub struct TilemapRenderer {
+    chunks: HashMap<IVec2, (u32, Texture)>,
+}
+
+impl TilemapRenderer {
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::new(),
+        }
+    }
+    pub fn render(
+        self: &mut Self,
+        tilemap: &Tilemap,
+        camera: &Camera,
+        canvas: &mut Canvas<Window>,
+        texture_manager: &mut TextureManager,
+        texture_creator: &TextureCreator<WindowContext>,
+    ) {
+        puffin::profile_scope!("render_tilemap");
+        let chunk_width = tilemap.tile_width * CHUNK_SIZE as u32;
+        let chunk_height = tilemap.tile_height * CHUNK_SIZE as u32;
+        // cached by chunk keys so scrolling the map doesn't mix up textures
+        let visible: Vec<(IVec2, IVec2)> = tilemap
+            .visible_chunks(camera)
+            .map(|chunk| (chunk, tilemap.chunk_key(chunk)))
+            .collect();
+        for &(chunk, key) in visible.iter() {
+            let version = tilemap.chunk_version(chunk);
+            if self
+                .chunks
+                .get(&key)
+                .map_or(true, |(cached, _)| *cached != version)
+            {
+                let texture = Self::bake(tilemap, chunk, canvas, texture_manager, texture_creator);
+                if let Some((_, old)) = self.chunks.insert(key, (version, texture)) {
+                    unsafe { old.destroy() };
+                }
+            }
+            let (_, texture) = &self.chunks[&key];
+            let position = tilemap.tile_to_world(chunk * CHUNK_SIZE as i32).as_ivec2();
+            let dst = Rect::new(
+                position.x + camera.x(),
+                position.y + camera.y(),
+                chunk_width,
+                chunk_height,
+            );
+            let _ = canvas.copy(texture, None, dst);
+        }
+        // keep a ring around the screen cached, free the rest
+        let evicted: Vec<IVec2> = self
+            .chunks
+            .keys()
+            .filter(|key| {
+                !visible
+                    .iter()
+                    .any(|(_, visible)| (**key - *visible).abs().max_element() <= 1)
+            })
+            .copied()
+            .collect();
+        for key in evicted {
+            if let Some((_, texture)) = self.chunks.remove(&key) {
+                // textures aren't freed on drop with unsafe_textures
+                unsafe { texture.destroy() };
+            }
+        }
+    }
+    fn bake(
+        tilemap: &Tilemap,
+        chunk: IVec2,
+        canvas: &mut Canvas<Window>,
+        texture_manager: &mut TextureManager,
+        texture_creator: &TextureCreator<WindowContext>,
+    ) -> Texture {
+        let mut texture = texture_creator
+            .create_texture_target(
+                PixelFormatEnum::RGBA8888,
+                tilemap.tile_width * CHUNK_SIZE as u32,
+                tilemap.tile_height * CHUNK_SIZE as u32,
+            )
+            .unwrap();
+        texture.set_blend_mode(BlendMode::Blend);
+        let _ = canvas.with_texture_canvas(&mut texture, |chunk_canvas| {
+            // empty tiles stay transparent so the water background shows through
+            chunk_canvas.set_draw_color(sdl2::pixels::Color::RGBA(0, 0, 0, 0));
+            chunk_canvas.clear();
+            let first = chunk * CHUNK_SIZE as i32;
+            let origin = tilemap.tile_to_world(first).as_ivec2();
+            for tile in tilemap.region(first, first + CHUNK_SIZE as i32 - 1) {
+                if let Some(&Tile {
+                    filename, autotile, ..
+                }) = tilemap.get(tile)
+                {
+                    let mut dst = tilemap.tile_rect(tile);
+                    dst.offset(-origin.x, -origin.y);
+                    let (filename, columns, rows) = match autotile {
+                        Autotile::None => (filename, 1, 1),
+                        Autotile::Edges(atlas) => (atlas, 4, 4),
+                        Autotile::Blob(atlas) => (atlas, 8, 6),
+                    };
+                    let texture = texture_manager.texture(filename, texture_creator);
+                    let query = texture.query();
+                    let (cell_width, cell_height) = (query.width / columns, query.height / rows);
+                    let cell = tilemap.variant(tile).unwrap_or(0) as u32;
+                    let src = Rect::new(
+                        (cell % columns * cell_width) as i32,
+                        (cell / columns * cell_height) as i32,
+                        cell_width,
+                        cell_height,
+                    );
+                    let _ = chunk_canvas.copy(texture, src, dst);
+                }
+            }
+        });
+        return texture;
+    }
+}
+
+// the level state is rebuilt on every death, win and season start
+impl Drop for TilemapRenderer {
+    fn drop(self: &mut Self) {
+        for (_, (_, texture)) in self.chunks.drain() {
+            // textures aren't freed on drop with unsafe_textures
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // not a multiple of CHUNK_SIZE so the last chunks are partly outside
+    const WIDTH: i32 = 10;
+    const HEIGHT: i32 = 7;
+
+    fn tilemap() -> Tilemap {
+        return Tilemap::new(WIDTH as u32, HEIGHT as u32, 64, 64);
+    }
+
+    fn name(tile: Option<&Tile>) -> Option<&'static str> {
+        return tile.map(|tile| tile.filename);
+    }
+
+    fn sorted(tiles: impl Iterator<Item = IVec2>) -> Vec<(i32, i32)> {
+        let mut tiles: Vec<(i32, i32)> = tiles.map(|tile| (tile.x, tile.y)).collect();
+        tiles.sort();
+        return tiles;
+    }
+
+    impl Tilemap {
+        fn fill(self: &mut Self, min: IVec2, max: IVec2, value: Option<Tile>) {
+            let id = value.map_or(0, |tile| self.register(tile));
+            let region: Vec<IVec2> = self.region(min, max).collect();
+            for tile in region {
+                self.write(tile, id);
+            }
+        }
+        // every tile 4-connected to start through tiles matching the predicate
+        fn flood(
+            self: &Self,
+            start: IVec2,
+            mut matches: impl FnMut(Option<&Tile>) -> bool,
+        ) -> Vec<IVec2> {
+            let mut visited = vec![false; self.width * self.height];
+            let mut found = vec![];
+            let mut stack = vec![start];
+            while let Some(tile) = stack.pop() {
+                let Some(current) = self.try_get(tile) else {
+                    continue;
+                };
+                let index = tile.x as usize * self.height + tile.y as usize;
+                if visited[index] || !matches(current) {
+                    continue;
+                }
+                visited[index] = true;
+                found.push(tile);
+                stack.extend(self.neighbours(tile));
+            }
+            return found;
+        }
+        // replaces the connected area of start's tile type, returns how many tiles changed
+        fn flood_fill(self: &mut Self, start: IVec2, value: Option<Tile>) -> usize {
+            let Some(id) = self.id(start) else {
+                return 0;
+            };
+            let area = self.flood(start, |tile| {
+                tile.map(|tile| tile.filename) == self.tile(id).map(|tile| tile.filename)
+            });
+            let id = value.map_or(0, |tile| self.register(tile));
+            for tile in area.iter() {
+                self.write(*tile, id);
+            }
+            return area.len();
+        }
+    }
+
+    #[test]
+    fn get_and_set_at_the_map_bounds() {
+        let mut tilemap = tilemap();
+        for tile in [IVec2::ZERO, IVec2::new(WIDTH - 1, HEIGHT - 1)] {
+            assert!(matches!(tilemap.try_get(tile), Some(None)));
+            assert_eq!(tilemap.set(tile, Some(GRASS)), Some(0));
+            assert_eq!(name(tilemap.get(tile)), Some(GRASS.filename));
+            assert_eq!(name(tilemap.try_get(tile).unwrap()), Some(GRASS.filename));
+            let grass = tilemap.register(GRASS);
+            assert_eq!(tilemap.set(tile, None), Some(grass));
+            assert!(matches!(tilemap.try_get(tile), Some(None)));
+        }
+        for tile in [IVec2::new(-1, 0), IVec2::new(WIDTH, HEIGHT)] {
+            assert_eq!(tilemap.set(tile, Some(GRASS)), None);
+            assert!(tilemap.get(tile).is_none());
+            assert!(tilemap.try_get(tile).is_none());
+        }
+    }
+
+    #[test]
+    fn neighbours_at_the_corners() {
+        let tilemap = tilemap();
+        assert_eq!(sorted(tilemap.neighbours(IVec2::ZERO)), [(0, 1), (1, 0)]);
+        assert_eq!(
+            sorted(tilemap.neighbours(IVec2::new(WIDTH - 1, HEIGHT - 1))),
+            [(WIDTH - 2, HEIGHT - 1), (WIDTH - 1, HEIGHT - 2)]
+        );
+        assert_eq!(tilemap.neighbours(IVec2::new(WIDTH - 1, 0)).count(), 2);
+        assert_eq!(tilemap.neighbours(IVec2::new(0, HEIGHT - 1)).count(), 2);
+    }
+
+    #[test]
+    fn region_is_clipped_at_the_map_edge() {
+        let tilemap = tilemap();
+        assert_eq!(
+            sorted(tilemap.region(IVec2::splat(-2), IVec2::ONE)),
+            [(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(
+            sorted(tilemap.region(
+                IVec2::new(WIDTH - 2, HEIGHT - 1),
+                IVec2::new(WIDTH + 3, HEIGHT + 3)
+            )),
+            [(WIDTH - 2, HEIGHT - 1), (WIDTH - 1, HEIGHT - 1)]
+        );
+        assert_eq!(
+            tilemap
+                .region(IVec2::new(-5, -5), IVec2::new(WIDTH + 5, HEIGHT + 5))
+                .count(),
+            (WIDTH * HEIGHT) as usize
+        );
+        assert_eq!(
+            tilemap
+                .region(IVec2::new(WIDTH, 0), IVec2::new(WIDTH + 2, 2))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn flood_from_an_edge_tile() {
+        let mut tilemap = tilemap();
+        tilemap.fill(IVec2::ZERO, IVec2::new(WIDTH - 1, HEIGHT - 1), Some(GRASS));
+        tilemap.fill(
+            IVec2::new(WIDTH - 1, 0),
+            IVec2::new(WIDTH - 1, HEIGHT - 1),
+            Some(PATH),
+        );
+        let is_path = |tile: Option<&Tile>| name(tile) == Some(PATH.filename);
+        let column = tilemap.flood(IVec2::new(WIDTH - 1, HEIGHT - 1), is_path);
+        assert_eq!(
+            sorted(column.into_iter()),
+            sorted((0..HEIGHT).map(|y| IVec2::new(WIDTH - 1, y)))
+        );
+        let is_grass = |tile: Option<&Tile>| name(tile) == Some(GRASS.filename);
+        assert_eq!(
+            tilemap.flood(IVec2::ZERO, is_grass).len(),
+            ((WIDTH - 1) * HEIGHT) as usize
+        );
+        assert!(tilemap.flood(IVec2::new(-1, 0), |_| true).is_empty());
+        assert_eq!(
+            tilemap.flood_fill(IVec2::new(WIDTH - 1, 0), Some(GRASS)),
+            HEIGHT as usize
+        );
+        assert_eq!(
+            tilemap.flood(IVec2::ZERO, is_grass).len(),
+            (WIDTH * HEIGHT) as usize
+        );
+    }
+
+    #[test]
+    fn walks_out_of_a_cliff() {
+        let mut tilemap = tilemap();
+        tilemap.fill(IVec2::ZERO, IVec2::new(WIDTH - 1, HEIGHT - 1), Some(GRASS));
+        tilemap.fill(IVec2::new(2, 2), IVec2::new(4, 4), Some(CLIFF));
+        let no_props = |_: Vec2, _: Vec2| false;
+        // starts in the middle of the cliff, overlapping several of its tiles
+        let mut min = tilemap.tile_to_world(IVec2::new(3, 3)) - Vec2::splat(16.0);
+        let max = |min: Vec2| min + Vec2::splat(32.0);
+        for _ in 0..40 {
+            min += tilemap.slide(min, max(min), Vec2::new(5.0, 0.0), no_props);
+        }
+        assert!(tilemap.is_walkable(min, max(min)));
+        // once out it can't go back in
+        let back = tilemap.slide(min, max(min), Vec2::new(-64.0, 0.0), no_props);
+        assert!(tilemap.is_walkable(min + back, max(min + back)));
+        assert!(back.x > -64.0);
+        // never deeper into the cliff while stuck, half in at its left edge
+        let start = tilemap.tile_to_world(IVec2::new(2, 3)) + Vec2::new(-16.0, 16.0);
+        let deeper = tilemap.slide(start, max(start), Vec2::new(8.0, 0.0), no_props);
+        assert_eq!(deeper, Vec2::ZERO);
+    }
+
+    #[test]
+    fn coordinates_round_trip_at_the_map_bounds() {
+        let tilemap = tilemap();
+        let (min, max) = tilemap.bounds();
+        let last = IVec2::new(WIDTH - 1, HEIGHT - 1);
+        for tile in [
+            IVec2::ZERO,
+            last,
+            IVec2::new(-1, 0),
+            IVec2::new(WIDTH, HEIGHT),
+        ] {
+            let corner = tilemap.tile_to_world(tile);
+            assert_eq!(tilemap.world_to_tile(corner), tile);
+            assert_eq!(tilemap.world_to_tile(corner + Vec2::splat(63.5)), tile);
+            let rect = tilemap.tile_rect(tile);
+            assert_eq!((rect.x() as f32, rect.y() as f32), (corner.x, corner.y));
+            assert_eq!((rect.width(), rect.height()), (64, 64));
+        }
+        assert_eq!(tilemap.tile_to_world(IVec2::ZERO), min);
+        assert_eq!(tilemap.tile_to_world(IVec2::new(WIDTH, HEIGHT)), max);
+        assert_eq!(tilemap.world_to_tile(min), IVec2::ZERO);
+        assert_eq!(
+            tilemap.world_to_tile(min - Vec2::splat(0.5)),
+            IVec2::splat(-1)
         );
+        assert_eq!(tilemap.world_to_tile(max - Vec2::splat(0.5)), last);
+        assert_eq!(tilemap.world_to_tile(max), IVec2::new(WIDTH, HEIGHT));
+        let rect = tilemap.tile_rect(last);
+        assert_eq!((rect.right() as f32, rect.bottom() as f32), (max.x, max.y));
     }
 }
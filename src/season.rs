@@ -0,0 +1,125 @@
+use sdl2::pixels::Color;
+
+use crate::{
+    director::{DifficultyCurve, EnemyKind},
+    worldgen::WorldgenParams,
+};
+
+// every season is the level1 game with its own tiles, enemies, music and mechanic
+#[derive(Clone, Copy, PartialEq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+// seasons unlock in this order, beating one unlocks the next
+pub const UNLOCK_ORDER: [Season; 4] = [
+    Season::Autumn,
+    Season::Winter,
+    Season::Spring,
+    Season::Summer,
+];
+
+impl Season {
+    // menu buttons left to right, 0 is quit
+    pub fn button(self: &Self) -> usize {
+        match self {
+            Self::Spring => 1,
+            Self::Summer => 2,
+            Self::Autumn => 3,
+            Self::Winter => 4,
+        }
+    }
+    pub fn from_button(button: usize) -> Option<Self> {
+        UNLOCK_ORDER
+            .into_iter()
+            .find(|season| season.button() == button)
+    }
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::Spring => "spring",
+            Self::Summer => "summer",
+            Self::Autumn => "autumn",
+            Self::Winter => "winter",
+        }
+    }
+    pub fn next(self: &Self) -> Option<Self> {
+        let index = UNLOCK_ORDER.iter().position(|season| season == self)?;
+        UNLOCK_ORDER.get(index + 1).copied()
+    }
+    // generation profile, see res/worldgen/autumn.json for every field
+    pub fn worldgen(self: &Self) -> WorldgenParams {
+        let profile = match self {
+            // wide meadows with thin winding trails
+            Self::Spring => "res/worldgen/spring.json",
+            // broad dunes, lots of sand to cross between the shade
+            Self::Summer => "res/worldgen/summer.json",
+            Self::Autumn => "res/worldgen/autumn.json",
+            // long frozen rivers, fewer dash crystals
+            Self::Winter => "res/worldgen/winter.json",
+        };
+        WorldgenParams::load(profile).unwrap_or_else(|error| {
+            println!("Couldn't load generation profile {}, using defaults", error);
+            WorldgenParams::default()
+        })
+    }
+    pub fn difficulty(self: &Self) -> DifficultyCurve {
+        match self {
+            // mostly ghosts, slow shooters
+            Self::Spring => DifficultyCurve {
+                shooters: &[EnemyKind::Orbit, EnemyKind::Spinner, EnemyKind::Skirmisher],
+                orbit_chance: 0.2,
+                max_orbit_chance: 0.4,
+                ..Default::default()
+            },
+            // fast waves of bullet hell shooters
+            Self::Summer => DifficultyCurve {
+                spawn_rate: 1.5,
+                max_spawn_rate: 8.0,
+                shooters: &[EnemyKind::Spreader, EnemyKind::Bloomer, EnemyKind::Spinner],
+                orbit_chance: 0.4,
+                max_orbit_chance: 0.7,
+                ..Default::default()
+            },
+            Self::Autumn => DifficultyCurve::default(),
+            // few enemies, long range ones and lurkers hiding in the snow
+            Self::Winter => DifficultyCurve {
+                cap: 60,
+                cap_growth: 30,
+                shooters: &[EnemyKind::Sniper, EnemyKind::Lurker, EnemyKind::Skirmisher],
+                ..Default::default()
+            },
+        }
+    }
+    // looped while the level plays
+    pub fn music(self: &Self) -> &'static str {
+        match self {
+            Self::Spring => "res/music_spring.wav",
+            Self::Summer => "res/music_summer.wav",
+            Self::Autumn => "res/music.wav",
+            Self::Winter => "res/music_winter.wav",
+        }
+    }
+    // water color under the map
+    pub fn background(self: &Self) -> Color {
+        match self {
+            Self::Spring => Color::RGB(38, 62, 96),
+            Self::Summer => Color::RGB(28, 92, 120),
+            Self::Autumn => Color::RGB(39, 9, 31),
+            Self::Winter => Color::RGB(70, 96, 128),
+        }
+    }
+    // spring, collected dash crystals grow back after this many seconds
+    pub fn regrowth_delay(self: &Self) -> Option<f32> {
+        match self {
+            Self::Spring => Some(20.0),
+            _ => None,
+        }
+    }
+    // summer, standing in heat zones overheats the player, see TileProperties::heat
+    pub fn overheats(self: &Self) -> bool {
+        *self == Self::Summer
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2};
+
+#[derive(Clone, Copy)]
+pub struct Entry {
+    pub id: hecs::Entity,
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+// uniform grid bucketing entity bounds by cell, broadphase for collisions and
+// neighbour lookups, moving entities are rebuilt every frame, static ones kept
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<Entry>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+    // keeps buckets allocated so rebuilding every frame doesn't allocate, buckets left
+    // empty since the last clear are dropped so roaming entities don't grow the grid
+    pub fn clear(self: &mut Self) {
+        self.cells.retain(|_, bucket| !bucket.is_empty());
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+    }
+    pub fn insert_rect(self: &mut Self, id: hecs::Entity, position: Vec2, size: Vec2) {
+        let entry = Entry {
+            id,
+            min: position,
+            max: position + size,
+        };
+        let min = self.cell(entry.min);
+        let max = self.cell(entry.max);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.cells.entry(IVec2::new(x, y)).or_default().push(entry);
+            }
+        }
+    }
+    // for static entities kept in the grid between frames
+    pub fn remove(self: &mut Self, entry: &Entry) {
+        let min = self.cell(entry.min);
+        let max = self.cell(entry.max);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let cell = IVec2::new(x, y);
+                if let Some(bucket) = self.cells.get_mut(&cell) {
+                    bucket.retain(|other| other.id != entry.id);
+                    if bucket.is_empty() {
+                        self.cells.remove(&cell);
+                    }
+                }
+            }
+        }
+    }
+    // every entry whose bounds touch the rect, each reported once
+    pub fn query_rect(self: &Self, min: Vec2, max: Vec2) -> impl Iterator<Item = Entry> + '_ {
+        let min_cell = self.cell(min);
+        let max_cell = self.cell(max);
+        (min_cell.x..=max_cell.x)
+            .flat_map(move |x| (min_cell.y..=max_cell.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(move |cell| self.cells.get(&cell).map(|bucket| (cell, bucket)))
+            .flat_map(move |(cell, bucket)| {
+                bucket.iter().copied().filter(move |entry| {
+                    entry.min.x <= max.x
+                        && min.x <= entry.max.x
+                        && entry.min.y <= max.y
+                        && min.y <= entry.max.y
+                        // entries spanning many cells only count in the cell holding
+                        // the top left corner of the overlap
+                        && self.cell(entry.min.max(min)) == cell
+                })
+            })
+    }
+    // every entry whose bounds touch the circle
+    pub fn query_circle(
+        self: &Self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = Entry> + '_ {
+        self.query_rect(center - Vec2::splat(radius), center + Vec2::splat(radius))
+            .filter(move |entry| {
+                center.clamp(entry.min, entry.max).distance_squared(center) <= radius * radius
+            })
+    }
+    fn cell(self: &Self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+}
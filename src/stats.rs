@@ -0,0 +1,41 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use serde::Serialize;
+
+const STATS_PATH: &str = "saves/runs.jsonl";
+
+// how a run went, every finished run is appended to STATS_PATH as a line of json
+#[derive(Serialize)]
+pub struct RunStats {
+    pub season: &'static str,
+    pub map: &'static str, // generated, tiled or endless
+    pub won: bool,
+    pub duration: f32, // in seconds
+    pub points: u32,
+    pub explored_percent: Option<f32>, // of the whole map, None in endless worlds
+    pub explored_tiles: usize,
+}
+
+impl RunStats {
+    pub fn save(self: &Self) {
+        if let Err(error) = self.append() {
+            println!("Couldn't save run stats {}: {}", STATS_PATH, error);
+        }
+    }
+    fn append(self: &Self) -> Result<(), String> {
+        let line = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        if let Some(directory) = Path::new(STATS_PATH).parent() {
+            fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(STATS_PATH)
+            .map_err(|error| error.to_string())?;
+        return writeln!(file, "{}", line).map_err(|error| error.to_string());
+    }
+}
@@ -0,0 +1,80 @@
+use glam::Vec2;
+use rand::{rngs::ThreadRng, Rng};
+
+use crate::spatial::SpatialGrid;
+
+// push away from neighbours, stronger the closer they are
+pub fn separation(id: hecs::Entity, position: Vec2, grid: &SpatialGrid, radius: f32) -> Vec2 {
+    let mut force = Vec2::ZERO;
+    for other in grid.query_circle(position, radius) {
+        if other.id == id {
+            continue;
+        }
+        let offset = position - other.min;
+        let distance = offset.length();
+        let direction = if distance > 0.0 {
+            offset / distance
+        } else {
+            // perfectly stacked, split them using id so both don't pick the same side
+            let angle = id.id() as f32;
+            Vec2::new(angle.cos(), angle.sin())
+        };
+        force += direction * (1.0 - distance / radius).max(0.0);
+    }
+    force
+}
+
+// pull toward centre of neighbours so the group moves as a loose pack
+pub fn cohesion(id: hecs::Entity, position: Vec2, grid: &SpatialGrid, radius: f32) -> Vec2 {
+    let mut center = Vec2::ZERO;
+    let mut count = 0;
+    for other in grid.query_circle(position, radius) {
+        if other.id == id {
+            continue;
+        }
+        center += other.min;
+        count += 1;
+    }
+    if count == 0 {
+        return Vec2::ZERO;
+    }
+    ((center / count as f32) - position) / radius
+}
+
+// velocity circling the point offset is measured from, easing onto radius
+// from anywhere within band (inner, outer), a band not around radius is clamped to it
+pub fn orbit(
+    offset: Vec2,
+    radius: f32,
+    band: (f32, f32),
+    angular_speed: f32,
+    clockwise: bool,
+    speed: f32,
+) -> Vec2 {
+    // keeps the easing below finite when the band touches radius
+    let band_inner = band.0.min(radius - 1.0);
+    let band_outer = band.1.max(radius + 1.0);
+    let r = offset.length();
+    let radial = if r > 0.0 { offset / r } else { Vec2::X };
+    // y axis points down so perp() turns clockwise on screen
+    let tangent = if clockwise {
+        radial.perp()
+    } else {
+        -radial.perp()
+    };
+    let orbit_speed = angular_speed.to_radians() * radius;
+    let radial_speed = if r < band_inner {
+        speed
+    } else if r < radius {
+        (radius - r) / (radius - band_inner) * speed
+    } else {
+        (radius - r) / (band_outer - radius) * speed
+    };
+    (tangent * orbit_speed + radial * radial_speed).clamp_length_max(speed)
+}
+
+// random walk, heading drifts by up to jitter degrees per second
+pub fn wander(heading: &mut f32, jitter: f32, rng: &mut ThreadRng, dt: f32) -> Vec2 {
+    *heading += rng.gen_range(-jitter..=jitter) * dt;
+    Vec2::from_angle(heading.to_radians())
+}
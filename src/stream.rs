@@ -0,0 +1,294 @@
+use std::fs;
+
+use glam::{IVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    director::EnemyKind,
+    prop,
+    render::{Tilemap, CHUNK_SIZE},
+    tiled::{Spawn, SpawnKind},
+    worldgen::{self, Terrain, WorldgenParams},
+};
+
+// chunks across the streamed tilemap, enough that chunks load and unload far off screen
+const WINDOW_CHUNKS: u32 = 12;
+// chunks the player gets from the middle of the tilemap before it scrolls after them
+const SCROLL_DISTANCE: i32 = 2;
+// unloaded chunks of the current run, cleared when a new one starts
+const SAVE_DIR: &str = "saves/endless";
+
+// what an unloaded chunk keeps until the player comes back
+pub enum ChunkEntity {
+    Spawn(Spawn), // crystals, props and enemy spawn points
+    Enemy(EnemyKind, Vec2),
+    Regrowth(Vec2, f32), // dash crystal growing back and the time left
+}
+
+// save file of an unloaded chunk
+#[derive(Serialize, Deserialize)]
+struct ChunkSave {
+    explored: u64, // fog of war, a bit per tile
+    entities: Vec<SavedEntity>,
+}
+
+// save file entry of a chunk entity, kinds and props by name
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum SavedEntity {
+    PointCrystal {
+        x: f32,
+        y: f32,
+    },
+    DashCrystal {
+        x: f32,
+        y: f32,
+    },
+    SpawnPoint {
+        x: f32,
+        y: f32,
+        enemy: Option<String>,
+    },
+    Prop {
+        x: f32,
+        y: f32,
+        prop: String,
+    },
+    Enemy {
+        x: f32,
+        y: f32,
+        enemy: String,
+    },
+    Regrowth {
+        x: f32,
+        y: f32,
+        timer: f32,
+    },
+}
+
+impl SavedEntity {
+    fn save(entity: &ChunkEntity) -> Option<Self> {
+        return Some(match *entity {
+            ChunkEntity::Spawn(Spawn { kind, position }) => {
+                let (x, y) = (position.x, position.y);
+                match kind {
+                    SpawnKind::PlayerStart => return None,
+                    SpawnKind::PointCrystal => Self::PointCrystal { x, y },
+                    SpawnKind::DashCrystal => Self::DashCrystal { x, y },
+                    SpawnKind::Enemy(enemy) => Self::SpawnPoint {
+                        x,
+                        y,
+                        enemy: enemy.map(|enemy| enemy.name().to_string()),
+                    },
+                    SpawnKind::Prop(prop) => Self::Prop {
+                        x,
+                        y,
+                        prop: prop.name.to_string(),
+                    },
+                }
+            }
+            ChunkEntity::Enemy(enemy, position) => Self::Enemy {
+                x: position.x,
+                y: position.y,
+                enemy: enemy.name().to_string(),
+            },
+            ChunkEntity::Regrowth(position, timer) => Self::Regrowth {
+                x: position.x,
+                y: position.y,
+                timer,
+            },
+        });
+    }
+    fn load(self: &Self) -> Result<ChunkEntity, String> {
+        let enemy = |name: &str| {
+            return EnemyKind::from_name(name).ok_or_else(|| format!("unknown enemy {}", name));
+        };
+        let spawn = |kind, x, y| {
+            return ChunkEntity::Spawn(Spawn {
+                kind,
+                position: Vec2::new(x, y),
+            });
+        };
+        return Ok(match self {
+            Self::PointCrystal { x, y } => spawn(SpawnKind::PointCrystal, *x, *y),
+            Self::DashCrystal { x, y } => spawn(SpawnKind::DashCrystal, *x, *y),
+            Self::SpawnPoint { x, y, enemy: kind } => {
+                let kind = kind.as_deref().map(enemy).transpose()?;
+                spawn(SpawnKind::Enemy(kind), *x, *y)
+            }
+            Self::Prop { x, y, prop } => {
+                let prop = prop::find(prop).ok_or_else(|| format!("unknown prop {}", prop))?;
+                spawn(SpawnKind::Prop(prop), *x, *y)
+            }
+            Self::Enemy { x, y, enemy: kind } => {
+                ChunkEntity::Enemy(enemy(kind)?, Vec2::new(*x, *y))
+            }
+            Self::Regrowth { x, y, timer } => ChunkEntity::Regrowth(Vec2::new(*x, *y), *timer),
+        });
+    }
+}
+
+// what scrolling changed, chunks in world chunk coordinates
+pub struct Scroll {
+    pub unloaded: Vec<IVec2>,
+    pub loaded: Vec<ChunkEntity>,
+    pub explored: Vec<(IVec2, u64)>, // explored tiles of loaded chunks seen before
+    previous: (IVec2, IVec2),        // first and last chunk covered before
+    chunk_size: Vec2,
+}
+
+impl Scroll {
+    // the unloaded chunk whatever is at the position gets saved with, entities that
+    // wandered off the tilemap go with the closest chunk it covered
+    pub fn unloaded_chunk(self: &Self, position: Vec2) -> Option<IVec2> {
+        let chunk = (position / self.chunk_size)
+            .floor()
+            .as_ivec2()
+            .clamp(self.previous.0, self.previous.1);
+        return self.unloaded.contains(&chunk).then_some(chunk);
+    }
+}
+
+// endless world around a fixed size tilemap that scrolls with the player, chunks are
+// generated from the seed and their coordinate when they come into range and saved to
+// disk when they go out of it, so memory doesn't grow however far the player travels
+pub struct WorldStream {
+    seed: u64,
+    params: WorldgenParams,
+    terrain: Terrain,
+}
+
+impl WorldStream {
+    // the tilemap around the origin and the spawns of its chunks, saves of the last run
+    // are cleared
+    pub fn new(seed: u64, params: WorldgenParams) -> (Self, Tilemap, Vec<Spawn>) {
+        let _ = fs::remove_dir_all(SAVE_DIR);
+        if let Err(error) = fs::create_dir_all(SAVE_DIR) {
+            println!("Couldn't create {} {}", SAVE_DIR, error);
+        }
+        let stream = Self {
+            seed,
+            terrain: Terrain::new(seed, &params),
+            params,
+        };
+        let size = WINDOW_CHUNKS * CHUNK_SIZE as u32;
+        let mut tilemap =
+            Tilemap::new(size, size, stream.params.tile_size, stream.params.tile_size);
+        let mut spawns = vec![];
+        let (first, last) = Self::window(&tilemap);
+        for chunk in chunks(first, last) {
+            spawns.extend(stream.generate(&mut tilemap, chunk));
+        }
+        return (stream, tilemap, spawns);
+    }
+
+    // scrolls the tilemap once the player is far enough from its middle, chunks scrolled
+    // in get their tiles and their entities from the save or freshly generated
+    pub fn update(self: &Self, tilemap: &mut Tilemap, player_position: Vec2) -> Option<Scroll> {
+        let (first, last) = Self::window(tilemap);
+        let chunk_size = tilemap.chunk_size().as_vec2();
+        let player = (player_position / chunk_size).floor().as_ivec2();
+        let offset = player - (first + last + 1) / 2;
+        if offset.abs().max_element() < SCROLL_DISTANCE {
+            return None;
+        }
+        puffin::profile_scope!("scroll");
+        tilemap.scroll(offset);
+        let (new_first, new_last) = Self::window(tilemap);
+        let covered = |chunk: IVec2, (first, last): (IVec2, IVec2)| {
+            return chunk.cmpge(first).all() && chunk.cmple(last).all();
+        };
+        let unloaded = chunks(first, last)
+            .filter(|chunk| !covered(*chunk, (new_first, new_last)))
+            .collect();
+        let mut loaded = vec![];
+        let mut explored = vec![];
+        for chunk in chunks(new_first, new_last).filter(|chunk| !covered(*chunk, (first, last))) {
+            let spawns = self.generate(tilemap, chunk);
+            match self.read(chunk) {
+                Ok(Some((entities, mask))) => {
+                    loaded.extend(entities);
+                    explored.push((chunk, mask));
+                }
+                Ok(None) => loaded.extend(spawns.into_iter().map(ChunkEntity::Spawn)),
+                Err(error) => {
+                    println!("Couldn't load chunk {}, generating it again", error);
+                    loaded.extend(spawns.into_iter().map(ChunkEntity::Spawn));
+                }
+            }
+        }
+        // tiles next to the new chunks were autotiled against the map edge
+        let max = IVec2::new(tilemap.width() as i32, tilemap.height() as i32) - 1;
+        tilemap.retile(IVec2::ZERO, max);
+        return Some(Scroll {
+            unloaded,
+            loaded,
+            explored,
+            previous: (first, last),
+            chunk_size,
+        });
+    }
+
+    // what was left of the chunk when it was unloaded, saved even when empty so
+    // collected crystals don't come back
+    pub fn save(self: &Self, chunk: IVec2, entities: &[ChunkEntity], explored: u64) {
+        let saved = ChunkSave {
+            explored,
+            entities: entities.iter().filter_map(SavedEntity::save).collect(),
+        };
+        let path = save_path(chunk);
+        let result = serde_json::to_string(&saved)
+            .map_err(|error| error.to_string())
+            .and_then(|text| fs::write(&path, text).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            println!("Couldn't save chunk {}: {}", path, error);
+        }
+    }
+
+    // None for chunks never unloaded, read saves are removed as the chunk is live again
+    fn read(self: &Self, chunk: IVec2) -> Result<Option<(Vec<ChunkEntity>, u64)>, String> {
+        let path = save_path(chunk);
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let _ = fs::remove_file(&path);
+        let saved: ChunkSave =
+            serde_json::from_str(&text).map_err(|error| format!("{}: {}", path, error))?;
+        let entities = saved
+            .entities
+            .iter()
+            .map(|entity| {
+                entity
+                    .load()
+                    .map_err(|error| format!("{}: {}", path, error))
+            })
+            .collect::<Result<Vec<ChunkEntity>, String>>()?;
+        return Ok(Some((entities, saved.explored)));
+    }
+
+    fn generate(self: &Self, tilemap: &mut Tilemap, chunk: IVec2) -> Vec<Spawn> {
+        let (first, _) = Self::window(tilemap);
+        return worldgen::generate_chunk(
+            self.seed,
+            &self.params,
+            &self.terrain,
+            tilemap,
+            chunk,
+            (chunk - first) * CHUNK_SIZE as i32,
+        );
+    }
+
+    // first and last world chunk the tilemap covers
+    fn window(tilemap: &Tilemap) -> (IVec2, IVec2) {
+        let first = tilemap.position() / tilemap.chunk_size();
+        return (first, first + WINDOW_CHUNKS as i32 - 1);
+    }
+}
+
+fn chunks(first: IVec2, last: IVec2) -> impl Iterator<Item = IVec2> {
+    return (first.x..=last.x).flat_map(move |x| (first.y..=last.y).map(move |y| IVec2::new(x, y)));
+}
+
+fn save_path(chunk: IVec2) -> String {
+    return format!("{}/{}_{}.json", SAVE_DIR, chunk.x, chunk.y);
+}
@@ -0,0 +1,808 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path},
+};
+
+use glam::{IVec2, Vec2};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    director::EnemyKind,
+    prop::{self, Prop},
+    render::{self, Tile, Tilemap},
+};
+
+// top gid bits are Tiled's flip and rotation flags, tiles are always drawn unflipped
+const GID_MASK: u32 = 0x0FFF_FFFF;
+// empty tiles show the water under the map
+const EMPTY_TILE: &str = "res/water.png";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy)]
+pub enum SpawnKind {
+    PlayerStart,
+    PointCrystal,
+    DashCrystal,
+    Enemy(Option<EnemyKind>), // None lets the director pick
+    Prop(Prop),
+}
+
+impl SpawnKind {
+    // object class in the editor, the enemy kind is the object's "enemy" property
+    // and the prop's name its "prop" property
+    pub fn name(self: &Self) -> &'static str {
+        match self {
+            Self::PlayerStart => "player_start",
+            Self::PointCrystal => "point_crystal",
+            Self::DashCrystal => "dash_crystal",
+            Self::Enemy(_) => "enemy_spawn",
+            Self::Prop(_) => "prop",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Spawn {
+    pub kind: SpawnKind,
+    pub position: Vec2, // world position of the top left corner
+}
+
+pub struct TiledMap {
+    pub tilemap: Tilemap,
+    pub spawns: Vec<Spawn>,
+}
+
+// the parts of a Tiled map we use, .tmx files are read into the same structs
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct MapData {
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    infinite: bool,
+    orientation: String,
+    layers: Vec<LayerData>,
+    tilesets: Vec<TilesetData>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct LayerData {
+    #[serde(rename = "type")]
+    kind: String, // tilelayer, objectgroup or group
+    data: LayerTiles,
+    encoding: String,
+    compression: String,
+    objects: Vec<ObjectData>,
+    layers: Vec<LayerData>, // children of a group
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LayerTiles {
+    Gids(Vec<u32>),
+    Encoded(String),
+}
+
+impl Default for LayerTiles {
+    fn default() -> Self {
+        Self::Gids(vec![])
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TilesetData {
+    firstgid: u32,
+    source: String, // external tileset, relative to the map
+    tiles: Vec<TileDefinition>,
+}
+
+// a tile of a tileset, matched to our tiles by its image file name or a "tile"
+// property so maps can use any art, atlas tilesets need the property
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TileDefinition {
+    id: u32,
+    image: String,
+    properties: Vec<Property>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Property {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ObjectData {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    class: String, // Tiled 1.9 saved the type as class
+    x: f32,
+    y: f32,
+    height: f32,
+    gid: u32,
+    properties: Vec<Property>,
+}
+
+// reads a .tmj or .tmx map, errors are prefixed with the file name
+pub fn load(path: &str) -> Result<TiledMap, String> {
+    return read_map(Path::new(path)).map_err(|error| format!("{}: {}", path, error));
+}
+
+fn read_map(path: &Path) -> Result<TiledMap, String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    let map = match extension(path) {
+        "tmj" | "json" => serde_json::from_str(&text).map_err(|error| error.to_string())?,
+        "tmx" => parse_tmx(&text)?,
+        other => return Err(format!("unknown map format .{}", other)),
+    };
+    if map.infinite {
+        return Err("infinite maps are not supported".to_string());
+    }
+    if !map.orientation.is_empty() && map.orientation != "orthogonal" {
+        return Err(format!("{} maps are not supported", map.orientation));
+    }
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut tiles = HashMap::new();
+    for tileset in map.tilesets.iter() {
+        let external;
+        let definitions = if tileset.source.is_empty() {
+            &tileset.tiles
+        } else {
+            let source = directory.join(&tileset.source);
+            external = read_tileset(&source)
+                .map_err(|error| format!("{}: {}", source.display(), error))?;
+            &external.tiles
+        };
+        for definition in definitions {
+            let name = property(&definition.properties, "tile").unwrap_or(&definition.image);
+            if !name.is_empty() {
+                tiles.insert(tileset.firstgid + definition.id, find_tile(name)?);
+            }
+        }
+    }
+    let mut tilemap = Tilemap::new(map.width, map.height, map.tilewidth, map.tileheight);
+    let mut spawns = vec![];
+    read_layers(&map.layers, &tiles, &mut tilemap, &mut spawns)?;
+    return Ok(TiledMap { tilemap, spawns });
+}
+
+// later layers draw over earlier ones, groups are flattened
+fn read_layers(
+    layers: &[LayerData],
+    tiles: &HashMap<u32, Option<Tile>>,
+    tilemap: &mut Tilemap,
+    spawns: &mut Vec<Spawn>,
+) -> Result<(), String> {
+    let width = tilemap.width();
+    let origin = tilemap.position().as_vec2();
+    for layer in layers {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                let gids = decode(layer)?;
+                if gids.len() != width * tilemap.height() {
+                    return Err(format!("tile layer has {} tiles", gids.len()));
+                }
+                for (index, gid) in gids.into_iter().enumerate() {
+                    let gid = gid & GID_MASK;
+                    if gid == 0 {
+                        continue;
+                    }
+                    let tile = *tiles
+                        .get(&gid)
+                        .ok_or_else(|| format!("tile {} has no image or tile property", gid))?;
+                    let position = IVec2::new((index % width) as i32, (index / width) as i32);
+                    tilemap.set(position, tile);
+                }
+            }
+            "objectgroup" => {
+                for object in layer.objects.iter() {
+                    if let Some(kind) = spawn_kind(object)? {
+                        // tile objects are anchored at their bottom left corner
+                        let y = if object.gid != 0 {
+                            object.y - object.height
+                        } else {
+                            object.y
+                        };
+                        spawns.push(Spawn {
+                            kind,
+                            position: origin + Vec2::new(object.x, y),
+                        });
+                    }
+                }
+            }
+            "group" => read_layers(&layer.layers, tiles, tilemap, spawns)?,
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+// objects we don't know are ignored so designers can leave notes in the map
+fn spawn_kind(object: &ObjectData) -> Result<Option<SpawnKind>, String> {
+    let class = [&object.class, &object.kind, &object.name]
+        .into_iter()
+        .find(|name| !name.is_empty());
+    let kind = match class.map(|name| name.as_str()) {
+        Some("player_start") => SpawnKind::PlayerStart,
+        Some("point_crystal") => SpawnKind::PointCrystal,
+        Some("dash_crystal") => SpawnKind::DashCrystal,
+        Some("enemy_spawn") => match property(&object.properties, "enemy") {
+            Some(name) => SpawnKind::Enemy(Some(
+                EnemyKind::from_name(name).ok_or_else(|| format!("unknown enemy {}", name))?,
+            )),
+            None => SpawnKind::Enemy(None),
+        },
+        Some("prop") => {
+            let name = property(&object.properties, "prop").ok_or("prop without a name")?;
+            SpawnKind::Prop(prop::find(name).ok_or_else(|| format!("unknown prop {}", name))?)
+        }
+        _ => return Ok(None),
+    };
+    return Ok(Some(kind));
+}
+
+// matches by file stem so both tile names and image paths work
+pub fn find_tile(name: &str) -> Result<Option<Tile>, String> {
+    let stem = |name: &str| Path::new(name).file_stem().map(|stem| stem.to_os_string());
+    if stem(name) == stem(EMPTY_TILE) {
+        return Ok(None);
+    }
+    return render::TILES
+        .into_iter()
+        .find(|tile| stem(tile.filename) == stem(name))
+        .map(Some)
+        .ok_or_else(|| format!("no tile matches {}", name));
+}
+
+fn property<'a>(properties: &'a [Property], name: &str) -> Option<&'a str> {
+    return properties
+        .iter()
+        .find(|property| property.name == name)
+        .and_then(|property| property.value.as_str());
+}
+
+fn extension(path: &Path) -> &str {
+    return path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+}
+
+fn read_tileset(path: &Path) -> Result<TilesetData, String> {
+    let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    return match extension(path) {
+        "tsj" | "json" => serde_json::from_str(&text).map_err(|error| error.to_string()),
+        "tsx" => {
+            let document = roxmltree::Document::parse(&text).map_err(|error| error.to_string())?;
+            tmx_tileset(document.root_element())
+        }
+        other => Err(format!("unknown tileset format .{}", other)),
+    };
+}
+
+fn decode(layer: &LayerData) -> Result<Vec<u32>, String> {
+    let text = match &layer.data {
+        LayerTiles::Gids(gids) => return Ok(gids.clone()),
+        LayerTiles::Encoded(text) => text,
+    };
+    return match (layer.encoding.as_str(), layer.compression.as_str()) {
+        ("csv", _) => text
+            .split(',')
+            .map(|gid| {
+                gid.trim()
+                    .parse()
+                    .map_err(|_| format!("invalid tile {}", gid.trim()))
+            })
+            .collect(),
+        ("base64", "") => {
+            let mut bytes = vec![];
+            let (mut buffer, mut bits) = (0_u32, 0);
+            for character in text
+                .bytes()
+                .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+            {
+                let value = BASE64
+                    .iter()
+                    .position(|&other| other == character)
+                    .ok_or("invalid base64 tile data")?;
+                buffer = (buffer << 6 | value as u32) & 0xFFFF;
+                bits += 6;
+                if bits >= 8 {
+                    bits -= 8;
+                    bytes.push((buffer >> bits) as u8);
+                }
+            }
+            Ok(bytes
+                .chunks_exact(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect())
+        }
+        ("base64", compression) => Err(format!(
+            "{} compressed layers are not supported",
+            compression
+        )),
+        (encoding, _) => Err(format!("unknown tile layer encoding {}", encoding)),
+    };
+}
+
+fn parse_tmx(text: &str) -> Result<MapData, String> {
+    let document = roxmltree::Document::parse(text).map_err(|error| error.to_string())?;
+    let root = document.root_element();
+    return Ok(MapData {
+        width: number(root, "width")?,
+        height: number(root, "height")?,
+        tilewidth: number(root, "tilewidth")?,
+        tileheight: number(root, "tileheight")?,
+        infinite: root.attribute("infinite") == Some("1"),
+        orientation: root.attribute("orientation").unwrap_or("").to_string(),
+        layers: tmx_layers(root)?,
+        tilesets: children(root, "tileset")
+            .map(tmx_tileset)
+            .collect::<Result<_, _>>()?,
+    });
+}
+
+fn tmx_layers(node: roxmltree::Node) -> Result<Vec<LayerData>, String> {
+    let mut layers = vec![];
+    for child in node.children().filter(|child| child.is_element()) {
+        let layer = match child.tag_name().name() {
+            "layer" => {
+                let data = children(child, "data")
+                    .next()
+                    .ok_or("tile layer without data")?;
+                let encoding = data.attribute("encoding").unwrap_or("");
+                LayerData {
+                    kind: "tilelayer".to_string(),
+                    // without an encoding every tile is its own element
+                    data: if encoding.is_empty() {
+                        LayerTiles::Gids(
+                            children(data, "tile")
+                                .map(|tile| number(tile, "gid"))
+                                .collect::<Result<_, _>>()?,
+                        )
+                    } else {
+                        LayerTiles::Encoded(data.text().unwrap_or("").to_string())
+                    },
+                    encoding: encoding.to_string(),
+                    compression: data.attribute("compression").unwrap_or("").to_string(),
+                    ..Default::default()
+                }
+            }
+            "objectgroup" => LayerData {
+                kind: "objectgroup".to_string(),
+                objects: children(child, "object")
+                    .map(|object| {
+                        Ok(ObjectData {
+                            name: object.attribute("name").unwrap_or("").to_string(),
+                            kind: object.attribute("type").unwrap_or("").to_string(),
+                            class: object.attribute("class").unwrap_or("").to_string(),
+                            x: number(object, "x")?,
+                            y: number(object, "y")?,
+                            height: number(object, "height")?,
+                            gid: number(object, "gid")?,
+                            properties: tmx_properties(object),
+                        })
+                    })
+                    .collect::<Result<_, String>>()?,
+                ..Default::default()
+            },
+            "group" => LayerData {
+                kind: "group".to_string(),
+                layers: tmx_layers(child)?,
+                ..Default::default()
+            },
+            _ => continue,
+        };
+        layers.push(layer);
+    }
+    return Ok(layers);
+}
+
+fn tmx_tileset(node: roxmltree::Node) -> Result<TilesetData, String> {
+    return Ok(TilesetData {
+        firstgid: number(node, "firstgid")?,
+        source: node.attribute("source").unwrap_or("").to_string(),
+        tiles: children(node, "tile")
+            .map(|tile| {
+                Ok(TileDefinition {
+                    id: number(tile, "id")?,
+                    image: children(tile, "image")
+                        .next()
+                        .and_then(|image| image.attribute("source"))
+                        .unwrap_or("")
+                        .to_string(),
+                    properties: tmx_properties(tile),
+                })
+            })
+            .collect::<Result<_, String>>()?,
+    });
+}
+
+fn tmx_properties(node: roxmltree::Node) -> Vec<Property> {
+    return children(node, "properties")
+        .flat_map(|properties| children(properties, "property"))
+        .map(|property| Property {
+            name: property.attribute("name").unwrap_or("").to_string(),
+            // multiline strings are stored as text instead of the value attribute
+            value: Value::String(
+                property
+                    .attribute("value")
+                    .or(property.text())
+                    .unwrap_or("")
+                    .to_string(),
+            ),
+        })
+        .collect();
+}
+
+fn children<'a, 'input: 'a>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    return node
+        .children()
+        .filter(move |child| child.has_tag_name(name));
+}
+
+// missing attributes read as zero like Tiled's defaults
+fn number<T: std::str::FromStr + Default>(node: roxmltree::Node, name: &str) -> Result<T, String> {
+    return match node.attribute(name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid {} on <{}>", name, node.tag_name().name())),
+        None => Ok(T::default()),
+    };
+}
+
+// writes the map and spawns as .tmj or .tmx depending on the extension, tiles go
+// into one image collection tileset pointing at our tile images
+pub fn save(path: &str, tilemap: &Tilemap, spawns: &[Spawn]) -> Result<(), String> {
+    let file = Path::new(path);
+    let text = match extension(file) {
+        "tmj" | "json" => tmj(file, tilemap, spawns),
+        "tmx" => tmx(file, tilemap, spawns),
+        other => return Err(format!("{}: unknown map format .{}", path, other)),
+    };
+    if let Some(directory) = file.parent() {
+        fs::create_dir_all(directory).map_err(|error| format!("{}: {}", path, error))?;
+    }
+    return fs::write(file, text).map_err(|error| format!("{}: {}", path, error));
+}
+
+fn gids(tilemap: &Tilemap) -> Vec<u32> {
+    let mut gids = vec![];
+    for y in 0..tilemap.height() as i32 {
+        for x in 0..tilemap.width() as i32 {
+            // first gid is 1 so gids match tile ids
+            gids.push(tilemap.id(IVec2::new(x, y)).unwrap_or(0) as u32);
+        }
+    }
+    return gids;
+}
+
+// tile images are relative to the working directory, Tiled wants them relative to the map
+fn image_path(map: &Path, image: &str) -> String {
+    let directory = map.parent().unwrap_or(Path::new(""));
+    if directory.is_absolute() {
+        return std::env::current_dir()
+            .map(|current| current.join(image).display().to_string())
+            .unwrap_or(image.to_string());
+    }
+    let depth = directory
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .count();
+    return "../".repeat(depth) + image;
+}
+
+fn tmj(path: &Path, tilemap: &Tilemap, spawns: &[Spawn]) -> String {
+    let origin = tilemap.position().as_vec2();
+    let tiles: Vec<Value> = tilemap
+        .tiles()
+        .iter()
+        .enumerate()
+        .map(|(id, tile)| {
+            json!({
+                "id": id,
+                "image": image_path(path, tile.filename),
+                "imagewidth": tilemap.tile_width,
+                "imageheight": tilemap.tile_height,
+            })
+        })
+        .collect();
+    let objects: Vec<Value> = spawns
+        .iter()
+        .enumerate()
+        .map(|(id, spawn)| {
+            let position = spawn.position - origin;
+            let properties: Vec<Value> = match spawn.kind {
+                SpawnKind::Enemy(Some(kind)) => {
+                    vec![json!({"name": "enemy", "type": "string", "value": kind.name()})]
+                }
+                SpawnKind::Prop(prop) => {
+                    vec![json!({"name": "prop", "type": "string", "value": prop.name})]
+                }
+                _ => vec![],
+            };
+            json!({
+                "id": id + 1,
+                "name": spawn.kind.name(),
+                "type": spawn.kind.name(),
+                "point": true,
+                "x": position.x,
+                "y": position.y,
+                "width": 0,
+                "height": 0,
+                "rotation": 0,
+                "visible": true,
+                "properties": properties,
+            })
+        })
+        .collect();
+    let map = json!({
+        "type": "map",
+        "version": "1.10",
+        "orientation": "orthogonal",
+        "renderorder": "right-down",
+        "width": tilemap.width(),
+        "height": tilemap.height(),
+        "tilewidth": tilemap.tile_width,
+        "tileheight": tilemap.tile_height,
+        "infinite": false,
+        "nextlayerid": 3,
+        "nextobjectid": spawns.len() + 1,
+        "tilesets": [{
+            "firstgid": 1,
+            "name": "tiles",
+            "tilewidth": tilemap.tile_width,
+            "tileheight": tilemap.tile_height,
+            "tilecount": tiles.len(),
+            "columns": 0,
+            "margin": 0,
+            "spacing": 0,
+            "grid": {"orientation": "orthogonal", "width": 1, "height": 1},
+            "tiles": tiles,
+        }],
+        "layers": [
+            {
+                "id": 1,
+                "name": "terrain",
+                "type": "tilelayer",
+                "width": tilemap.width(),
+                "height": tilemap.height(),
+                "x": 0,
+                "y": 0,
+                "opacity": 1,
+                "visible": true,
+                "data": gids(tilemap),
+            },
+            {
+                "id": 2,
+                "name": "spawns",
+                "type": "objectgroup",
+                "draworder": "topdown",
+                "x": 0,
+                "y": 0,
+                "opacity": 1,
+                "visible": true,
+                "objects": objects,
+            },
+        ],
+    });
+    return serde_json::to_string_pretty(&map).unwrap();
+}
+
+fn tmx(path: &Path, tilemap: &Tilemap, spawns: &[Spawn]) -> String {
+    let origin = tilemap.position().as_vec2();
+    let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    text += &format!(
+        "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\" nextlayerid=\"3\" nextobjectid=\"{}\">\n",
+        tilemap.width(),
+        tilemap.height(),
+        tilemap.tile_width,
+        tilemap.tile_height,
+        spawns.len() + 1
+    );
+    text += &format!(
+        " <tileset firstgid=\"1\" name=\"tiles\" tilewidth=\"{}\" tileheight=\"{}\" tilecount=\"{}\" columns=\"0\">\n  <grid orientation=\"orthogonal\" width=\"1\" height=\"1\"/>\n",
+        tilemap.tile_width,
+        tilemap.tile_height,
+        tilemap.tiles().len()
+    );
+    for (id, tile) in tilemap.tiles().iter().enumerate() {
+        text += &format!(
+            "  <tile id=\"{}\">\n   <image width=\"{}\" height=\"{}\" source=\"{}\"/>\n  </tile>\n",
+            id,
+            tilemap.tile_width,
+            tilemap.tile_height,
+            image_path(path, tile.filename)
+        );
+    }
+    text += " </tileset>\n";
+    text += &format!(
+        " <layer id=\"1\" name=\"terrain\" width=\"{}\" height=\"{}\">\n  <data encoding=\"csv\">\n",
+        tilemap.width(),
+        tilemap.height()
+    );
+    let rows: Vec<String> = gids(tilemap)
+        .chunks(tilemap.width())
+        .map(|row| {
+            row.iter()
+                .map(|gid| gid.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect();
+    text += &rows.join(",\n");
+    text += "\n</data>\n </layer>\n <objectgroup id=\"2\" name=\"spawns\">\n";
+    for (id, spawn) in spawns.iter().enumerate() {
+        let position = spawn.position - origin;
+        text += &format!(
+            "  <object id=\"{}\" name=\"{}\" type=\"{}\" x=\"{}\" y=\"{}\">\n",
+            id + 1,
+            spawn.kind.name(),
+            spawn.kind.name(),
+            position.x,
+            position.y
+        );
+        let property = match spawn.kind {
+            SpawnKind::Enemy(Some(kind)) => Some(("enemy", kind.name())),
+            SpawnKind::Prop(prop) => Some(("prop", prop.name)),
+            _ => None,
+        };
+        if let Some((name, value)) = property {
+            text += &format!(
+                "   <properties>\n    <property name=\"{}\" value=\"{}\"/>\n   </properties>\n",
+                name, value
+            );
+        }
+        text += "   <point/>\n  </object>\n";
+    }
+    text += " </objectgroup>\n</map>\n";
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{CLIFF, GRASS, PATH};
+
+    // gids 1 and 2, 3 flipped horizontally and an empty tile, as Tiled 1.10 writes them
+    const TMX_LAYERS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.10.2" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="64" tileheight="64" infinite="0" nextlayerid="3" nextobjectid="1">
+ <layer id="1" name="base64" width="2" height="2">
+  <data encoding="base64">
+   AQAAAAIAAAADAACAAAAAAA==
+  </data>
+ </layer>
+ <layer id="2" name="csv" width="2" height="2">
+  <data encoding="csv">
+1,2,
+2147483651,0
+</data>
+ </layer>
+</map>
+"#;
+    const TMJ_LAYER: &str = r#"{
+ "data":"AQAAAAIAAAADAACAAAAAAA==",
+ "encoding":"base64",
+ "height":2,
+ "id":1,
+ "name":"base64",
+ "opacity":1,
+ "type":"tilelayer",
+ "visible":true,
+ "width":2,
+ "x":0,
+ "y":0
+}"#;
+    const GIDS: [u32; 4] = [1, 2, 0x8000_0003, 0];
+
+    fn tilemap() -> Tilemap {
+        let mut tilemap = Tilemap::new(5, 3, 64, 64);
+        for (x, y, tile) in [(0, 0, GRASS), (1, 0, GRASS), (4, 0, PATH), (2, 2, CLIFF)] {
+            tilemap.set(IVec2::new(x, y), Some(tile));
+        }
+        return tilemap;
+    }
+
+    fn spawns(tilemap: &Tilemap) -> Vec<Spawn> {
+        let origin = tilemap.position().as_vec2();
+        return [
+            SpawnKind::PlayerStart,
+            SpawnKind::PointCrystal,
+            SpawnKind::DashCrystal,
+            SpawnKind::Enemy(None),
+            SpawnKind::Enemy(Some(EnemyKind::Sniper)),
+            SpawnKind::Prop(prop::ROCK),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(index, kind)| Spawn {
+            kind,
+            position: origin + Vec2::new(index as f32 * 40.5, 70.25),
+        })
+        .collect();
+    }
+
+    type Fingerprint = (
+        Vec<Option<&'static str>>,
+        Vec<(&'static str, &'static str, Vec2)>,
+    );
+
+    // every tile and spawn, enough to tell two maps apart
+    fn fingerprint(tilemap: &Tilemap, spawns: &[Spawn]) -> Fingerprint {
+        let tiles = tilemap
+            .region(
+                IVec2::ZERO,
+                IVec2::new(tilemap.width() as i32, tilemap.height() as i32),
+            )
+            .map(|tile| tilemap.get(tile).map(|tile| tile.filename))
+            .collect();
+        let spawns = spawns
+            .iter()
+            .map(|spawn| {
+                let detail = match spawn.kind {
+                    SpawnKind::Enemy(Some(kind)) => kind.name(),
+                    SpawnKind::Prop(prop) => prop.name,
+                    _ => "",
+                };
+                (spawn.kind.name(), detail, spawn.position)
+            })
+            .collect();
+        return (tiles, spawns);
+    }
+
+    fn round_trip(extension: &str) {
+        let path = std::env::temp_dir().join(format!(
+            "psytumn_round_trip_{}.{}",
+            std::process::id(),
+            extension
+        ));
+        let path = path.to_str().unwrap();
+        let tilemap = tilemap();
+        let spawns = spawns(&tilemap);
+        save(path, &tilemap, &spawns).unwrap();
+        let loaded = load(path);
+        let _ = fs::remove_file(path);
+        let loaded = loaded.unwrap();
+        assert_eq!(
+            (loaded.tilemap.width(), loaded.tilemap.height()),
+            (tilemap.width(), tilemap.height())
+        );
+        assert_eq!(
+            fingerprint(&loaded.tilemap, &loaded.spawns),
+            fingerprint(&tilemap, &spawns)
+        );
+    }
+
+    #[test]
+    fn tmj_round_trip() {
+        round_trip("tmj");
+    }
+
+    #[test]
+    fn tmx_round_trip() {
+        round_trip("tmx");
+    }
+
+    #[test]
+    fn decodes_tiled_layers() {
+        let map = parse_tmx(TMX_LAYERS).unwrap();
+        assert_eq!(map.layers.len(), 2);
+        for layer in map.layers.iter() {
+            assert_eq!(decode(layer).unwrap(), GIDS);
+        }
+        let layer: LayerData = serde_json::from_str(TMJ_LAYER).unwrap();
+        assert_eq!(decode(&layer).unwrap(), GIDS);
+    }
+}
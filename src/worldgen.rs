@@ -0,0 +1,1014 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+    fs,
+};
+
+use bracket_noise::prelude::{FastNoise, FractalType, NoiseType};
+use glam::{IVec2, Vec2};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    prop::{self, Prop},
+    render::{self, Tile, Tilemap},
+    tiled::{self, Spawn, SpawnKind},
+};
+
+const DIRECTIONS: [Vec2; 8] = [
+    Vec2::new(1.0, 0.0),
+    Vec2::new(1.0, 1.0),
+    Vec2::new(0.0, 1.0),
+    Vec2::new(-1.0, 1.0),
+    Vec2::new(-1.0, 0.0),
+    Vec2::new(-1.0, -1.0),
+    Vec2::new(0.0, -1.0),
+    Vec2::new(1.0, -1.0),
+];
+// random tiles tried per biome weighted placement before giving up
+const PLACEMENT_ATTEMPTS: u32 = 100;
+// dash crystal trails started per crystal before giving up, trails running straight
+// off the map or into a cliff place nothing
+const TRAIL_ATTEMPTS: u32 = 10;
+// random points tried per prop at density 1, about enough to fill the area
+const PROP_DARTS: f32 = 4.0;
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseKind {
+    Value,
+    Perlin,
+    Simplex,
+    Cubic,
+    Cellular,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct NoiseLayer {
+    pub kind: NoiseKind,
+    pub frequency: f32,
+    pub octaves: i32, // more than one makes it fractal
+    pub ridged: bool, // sharp ridges instead of smooth hills, fractal only
+    pub seed: u64,    // added to the level seed so layers don't line up
+}
+
+impl Default for NoiseLayer {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::Perlin,
+            frequency: 0.05,
+            octaves: 1,
+            ridged: false,
+            seed: 0,
+        }
+    }
+}
+
+impl NoiseLayer {
+    pub fn build(self: &Self, seed: u64) -> FastNoise {
+        let fractal = self.octaves > 1;
+        let mut noise = FastNoise::seeded(seed.wrapping_add(self.seed));
+        noise.set_noise_type(match (self.kind, fractal) {
+            (NoiseKind::Value, false) => NoiseType::Value,
+            (NoiseKind::Value, true) => NoiseType::ValueFractal,
+            (NoiseKind::Perlin, false) => NoiseType::Perlin,
+            (NoiseKind::Perlin, true) => NoiseType::PerlinFractal,
+            (NoiseKind::Simplex, false) => NoiseType::Simplex,
+            (NoiseKind::Simplex, true) => NoiseType::SimplexFractal,
+            (NoiseKind::Cubic, false) => NoiseType::Cubic,
+            (NoiseKind::Cubic, true) => NoiseType::CubicFractal,
+            (NoiseKind::Cellular, _) => NoiseType::Cellular,
+        });
+        noise.set_frequency(self.frequency);
+        noise.set_fractal_octaves(self.octaves);
+        if self.ridged {
+            noise.set_fractal_type(FractalType::RigidMulti);
+        }
+        return noise;
+    }
+}
+
+// the first biome whose elevation and moisture ranges contain a tile's noise values wins
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Biome {
+    pub name: String,
+    pub min_elevation: f32,
+    pub max_elevation: f32,
+    pub min_moisture: f32,
+    pub max_moisture: f32,
+    #[serde(deserialize_with = "tile_by_name")]
+    pub ground: Option<Tile>, // "water" leaves it empty
+    pub paths: f32,           // scales path_width, 0 keeps the path network out
+    pub decoration: f32,      // 0..1, how likely dash crystal trails start here
+    pub enemy_density: f32,   // 0..1, how likely enemy spawn points land here
+    pub props: Vec<PropRule>, // scattered in order, list big props first so they get room
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct PropRule {
+    #[serde(deserialize_with = "prop_by_name")]
+    pub prop: Prop,
+    #[serde(deserialize_with = "tiles_by_name")]
+    pub tiles: Vec<Option<Tile>>, // what it can stand on, the biome's ground when empty
+    pub spacing: f32, // least distance in px to other props
+    pub density: f32, // 0..1, 1 packs them as close as spacing allows
+}
+
+impl Default for PropRule {
+    fn default() -> Self {
+        Self {
+            prop: prop::BUSH,
+            tiles: vec![],
+            spacing: 128.0,
+            density: 0.5,
+        }
+    }
+}
+
+impl Default for Biome {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            min_elevation: f32::MIN,
+            max_elevation: f32::MAX,
+            min_moisture: f32::MIN,
+            max_moisture: f32::MAX,
+            ground: Some(render::GRASS),
+            paths: 1.0,
+            decoration: 0.5,
+            enemy_density: 0.5,
+            props: vec![],
+        }
+    }
+}
+
+impl Biome {
+    fn contains(self: &Self, elevation: f32, moisture: f32) -> bool {
+        return (self.min_elevation..self.max_elevation).contains(&elevation)
+            && (self.min_moisture..self.max_moisture).contains(&moisture);
+    }
+    fn allows(self: &Self, rule: &PropRule, tile: Option<&Tile>) -> bool {
+        let filename = |tile: Option<&Tile>| tile.map(|tile| tile.filename);
+        if rule.tiles.is_empty() {
+            return filename(tile) == filename(self.ground.as_ref());
+        }
+        return rule
+            .tiles
+            .iter()
+            .any(|allowed| filename(allowed.as_ref()) == filename(tile));
+    }
+}
+
+// loaded from a generation profile, missing fields keep their defaults
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct WorldgenParams {
+    pub width: u32, // in tiles
+    pub height: u32,
+    pub tile_size: u32,
+    #[serde(deserialize_with = "solid_tile_by_name")]
+    pub path: Tile,
+    // low elevation floods into lakes, values of the river noise near zero carve rivers
+    pub elevation: NoiseLayer,
+    pub moisture: NoiseLayer,
+    pub river: NoiseLayer,
+    pub lake_level: f32,
+    pub river_width: f32,
+    #[serde(deserialize_with = "tile_by_name")]
+    pub lakes: Option<Tile>,
+    #[serde(deserialize_with = "tile_by_name")]
+    pub rivers: Option<Tile>,
+    // detail values near zero become the path network
+    pub detail: NoiseLayer,
+    pub path_width: f32,
+    pub biomes: Vec<Biome>,
+    // dash crystals come in random walk trails
+    pub dash_crystals: u32,
+    pub dash_trail_step: f32,
+    pub dash_trail_chance: f64, // chance of another crystal after each one
+    // point crystals go on the path closest to the corners, clockwise from top left
+    pub point_crystals: u32,
+    // points the director spawns enemies around
+    pub enemy_spawns: u32,
+}
+
+impl Default for WorldgenParams {
+    fn default() -> Self {
+        Self {
+            width: 200,
+            height: 200,
+            tile_size: 64,
+            path: render::PATH,
+            elevation: NoiseLayer {
+                kind: NoiseKind::Simplex,
+                frequency: 0.015,
+                octaves: 3,
+                seed: 1,
+                ..Default::default()
+            },
+            moisture: NoiseLayer {
+                kind: NoiseKind::Simplex,
+                frequency: 0.02,
+                octaves: 2,
+                seed: 2,
+                ..Default::default()
+            },
+            river: NoiseLayer {
+                kind: NoiseKind::Simplex,
+                frequency: 0.012,
+                octaves: 3,
+                seed: 3,
+                ..Default::default()
+            },
+            lake_level: -0.3,
+            river_width: 0.015,
+            lakes: None,
+            rivers: None,
+            detail: NoiseLayer::default(),
+            path_width: 0.07,
+            biomes: vec![
+                // peaks are cliffs ringed by thorns, paths cut passes through both
+                Biome {
+                    name: "crags".to_string(),
+                    min_elevation: 0.45,
+                    ground: Some(render::CLIFF),
+                    decoration: 0.0,
+                    enemy_density: 0.0,
+                    ..Default::default()
+                },
+                Biome {
+                    name: "bramble".to_string(),
+                    min_elevation: 0.38,
+                    ground: Some(render::THORNS),
+                    decoration: 0.2,
+                    enemy_density: 0.3,
+                    ..Default::default()
+                },
+                Biome {
+                    name: "marsh".to_string(),
+                    min_moisture: 0.3,
+                    ground: None,
+                    paths: 0.5,
+                    decoration: 0.3,
+                    enemy_density: 0.2,
+                    ..Default::default()
+                },
+                Biome {
+                    name: "forest".to_string(),
+                    min_moisture: 0.05,
+                    paths: 0.6,
+                    decoration: 0.6,
+                    enemy_density: 0.8,
+                    props: vec![
+                        PropRule {
+                            prop: prop::TREE,
+                            spacing: 192.0,
+                            density: 0.7,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::BUSH,
+                            spacing: 128.0,
+                            density: 0.3,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::LEAVES,
+                            tiles: vec![Some(render::GRASS), Some(render::PATH)],
+                            spacing: 96.0,
+                            density: 0.5,
+                        },
+                    ],
+                    ..Default::default()
+                },
+                Biome {
+                    name: "clearing".to_string(),
+                    max_moisture: -0.25,
+                    ground: Some(render::PATH),
+                    paths: 0.0,
+                    decoration: 1.0,
+                    enemy_density: 0.3,
+                    props: vec![
+                        PropRule {
+                            prop: prop::ROCK,
+                            spacing: 256.0,
+                            density: 0.15,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::LEAVES,
+                            spacing: 128.0,
+                            density: 0.3,
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                Biome {
+                    name: "meadow".to_string(),
+                    props: vec![
+                        PropRule {
+                            prop: prop::TREE,
+                            spacing: 320.0,
+                            density: 0.15,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::ROCK,
+                            spacing: 256.0,
+                            density: 0.1,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::BUSH,
+                            spacing: 192.0,
+                            density: 0.2,
+                            ..Default::default()
+                        },
+                        PropRule {
+                            prop: prop::LEAVES,
+                            tiles: vec![Some(render::GRASS), Some(render::PATH)],
+                            spacing: 128.0,
+                            density: 0.2,
+                        },
+                    ],
+                    ..Default::default()
+                },
+            ],
+            dash_crystals: 800,
+            dash_trail_step: 400.0,
+            dash_trail_chance: 0.5,
+            point_crystals: 3,
+            enemy_spawns: 150,
+        }
+    }
+}
+
+impl WorldgenParams {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        return serde_json::from_str(&text).map_err(|error| format!("{}: {}", path, error));
+    }
+}
+
+// a seed's noise layers, every tile is classified on its own so any part of the world
+// can be generated without the rest
+pub struct Terrain {
+    elevation: FastNoise,
+    moisture: FastNoise,
+    river: FastNoise,
+    detail: FastNoise,
+}
+
+impl Terrain {
+    pub fn new(seed: u64, params: &WorldgenParams) -> Self {
+        return Self {
+            elevation: params.elevation.build(seed),
+            moisture: params.moisture.build(seed),
+            river: params.river.build(seed),
+            detail: params.detail.build(seed),
+        };
+    }
+    // ground of the tile and the index of its biome, None under lakes and rivers
+    pub fn sample(
+        self: &Self,
+        params: &WorldgenParams,
+        tile: IVec2,
+    ) -> (Option<Tile>, Option<usize>) {
+        let (fx, fy) = (tile.x as f32, tile.y as f32);
+        let height = self.elevation.get_noise(fx, fy);
+        if height < params.lake_level {
+            return (params.lakes, None);
+        }
+        if self.river.get_noise(fx, fy).abs() < params.river_width {
+            return (params.rivers, None);
+        }
+        let wetness = self.moisture.get_noise(fx, fy);
+        let Some(index) = params
+            .biomes
+            .iter()
+            .position(|biome| biome.contains(height, wetness))
+        else {
+            return (None, None);
+        };
+        let biome = &params.biomes[index];
+        if self.detail.get_noise(fx, fy).abs() < params.path_width * biome.paths {
+            return (Some(params.path), Some(index));
+        }
+        return (biome.ground, Some(index));
+    }
+}
+
+// biome index per tile of a rect of the tilemap, row by row
+struct BiomeMap {
+    origin: IVec2, // tilemap tile of the first entry
+    width: i32,
+    height: i32,
+    biomes: Vec<Option<usize>>,
+}
+
+impl BiomeMap {
+    fn new(origin: IVec2, width: i32, height: i32) -> Self {
+        return Self {
+            origin,
+            width,
+            height,
+            biomes: vec![None; (width * height) as usize],
+        };
+    }
+    fn index(self: &Self, tile: IVec2) -> Option<usize> {
+        let local = tile - self.origin;
+        if local.x < 0 || local.y < 0 || local.x >= self.width || local.y >= self.height {
+            return None;
+        }
+        return Some((local.y * self.width + local.x) as usize);
+    }
+    fn get(self: &Self, tile: IVec2) -> Option<usize> {
+        return self.index(tile).and_then(|index| self.biomes[index]);
+    }
+    fn set(self: &mut Self, tile: IVec2, biome: Option<usize>) {
+        if let Some(index) = self.index(tile) {
+            self.biomes[index] = biome;
+        }
+    }
+    fn tiles(self: &Self) -> impl Iterator<Item = IVec2> {
+        let (origin, width) = (self.origin, self.width);
+        return (0..self.height)
+            .flat_map(move |y| (0..width).map(move |x| origin + IVec2::new(x, y)));
+    }
+}
+
+pub struct GeneratedLevel {
+    pub tilemap: Tilemap,
+    pub spawns: Vec<Spawn>,
+}
+
+// same seed and params always give the same level
+pub fn generate(seed: u64, params: &WorldgenParams) -> GeneratedLevel {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut tilemap = Tilemap::new(
+        params.width,
+        params.height,
+        params.tile_size,
+        params.tile_size,
+    );
+    let terrain = Terrain::new(seed, params);
+    let mut biomes = BiomeMap::new(IVec2::ZERO, params.width as i32, params.height as i32);
+    for x in 0..params.width as i32 {
+        for y in 0..params.height as i32 {
+            let tile = IVec2::new(x, y);
+            let (ground, biome) = terrain.sample(params, tile);
+            biomes.set(tile, biome);
+            if ground.is_some() {
+                tilemap.set(tile, ground);
+            }
+        }
+    }
+
+    // the player starts in the middle of the map
+    let player_start = Vec2::ZERO;
+    let start = tilemap.world_to_tile(player_start);
+    if !is_safe(tilemap.get(start)) {
+        tilemap.set(start, Some(params.path));
+    }
+    let mut spawns = vec![Spawn {
+        kind: SpawnKind::PlayerStart,
+        position: player_start,
+    }];
+
+    let mut dash_crystals = 0;
+    let mut trails = 0;
+    while dash_crystals < params.dash_crystals
+        && trails < params.dash_crystals.saturating_mul(TRAIL_ATTEMPTS)
+    {
+        trails += 1;
+        let Some(tile) = pick_tile(&mut rng, params, &biomes, |biome| biome.decoration) else {
+            break;
+        };
+        let mut position = tilemap.tile_to_world(tile);
+        loop {
+            position += DIRECTIONS.choose(&mut rng).unwrap().normalize() * params.dash_trail_step;
+            // trails leaving the map or running into cliffs are cut short
+            let tile = tilemap.world_to_tile(position);
+            if !tilemap.contains(tile) || !is_walkable(tilemap.get(tile)) {
+                break;
+            }
+            spawns.push(Spawn {
+                kind: SpawnKind::DashCrystal,
+                position,
+            });
+            dash_crystals += 1;
+            if dash_crystals >= params.dash_crystals || !rng.gen_bool(params.dash_trail_chance) {
+                break;
+            }
+        }
+    }
+
+    // props come last and keep clear of the crystals
+    let reachable = reachable_from(&tilemap, start, &HashSet::new());
+    let corners = [
+        IVec2::ZERO,
+        IVec2::new(params.width as i32 - 1, 0),
+        IVec2::new(params.width as i32 - 1, params.height as i32 - 1),
+        IVec2::new(0, params.height as i32 - 1),
+    ];
+    let mut used = HashSet::new();
+    for index in 0..params.point_crystals as usize {
+        let corner = corners[index % corners.len()];
+        let free = reachable.iter().filter(|tile| !used.contains(*tile));
+        let on_path = free.clone().filter(|tile| {
+            tilemap.get(**tile).map(|tile| tile.filename) == Some(params.path.filename)
+        });
+        let tile = match closest(on_path, corner) {
+            Some(tile) => tile,
+            // no path left to reach, lay a path tile on the closest walkable one
+            None => {
+                let Some(tile) = closest(free, corner) else {
+                    break;
+                };
+                tilemap.set(tile, Some(params.path));
+                tile
+            }
+        };
+        used.insert(tile);
+        spawns.push(Spawn {
+            kind: SpawnKind::PointCrystal,
+            position: tilemap.tile_to_world(tile),
+        });
+    }
+
+    // water and anything the player can't walk on gets no spawn points
+    for _ in 0..params.enemy_spawns {
+        let Some(tile) = pick_tile(&mut rng, params, &biomes, |biome| biome.enemy_density) else {
+            break;
+        };
+        if tilemap
+            .get(tile)
+            .map_or(false, |tile| tile.properties.walkable)
+        {
+            spawns.push(Spawn {
+                kind: SpawnKind::Enemy(None),
+                position: tilemap.tile_to_world(tile),
+            });
+        }
+    }
+
+    scatter_props(&mut rng, &tilemap, params, &biomes, None, &mut spawns);
+
+    let level = GeneratedLevel { tilemap, spawns };
+    debug_assert!(
+        level.validate().is_ok(),
+        "seed {}: {}",
+        seed,
+        level.validate().unwrap_err()
+    );
+    return level;
+}
+
+// one chunk of an endless world written into the tilemap at `first`, its top left tile,
+// `chunk` is the chunk's world coordinate so the same seed and chunk always give the
+// same tiles and spawns, the terrain lines up across chunks and per map counts of
+// crystals and spawn points are spread by area
+pub fn generate_chunk(
+    seed: u64,
+    params: &WorldgenParams,
+    terrain: &Terrain,
+    tilemap: &mut Tilemap,
+    chunk: IVec2,
+    first: IVec2,
+) -> Vec<Spawn> {
+    let size = render::CHUNK_SIZE as i32;
+    let mut rng =
+        StdRng::seed_from_u64(seed ^ ((chunk.x as u32 as u64) << 32 | chunk.y as u32 as u64));
+    let mut biomes = BiomeMap::new(first, size, size);
+    for tile in biomes.tiles().collect::<Vec<IVec2>>() {
+        let (ground, biome) = terrain.sample(params, chunk * size + tile - first);
+        biomes.set(tile, biome);
+        tilemap.set(tile, ground);
+    }
+    let area = (
+        tilemap.tile_to_world(first),
+        tilemap.tile_to_world(first + size),
+    );
+    let inside = |position: Vec2| position.cmpge(area.0).all() && position.cmplt(area.1).all();
+    let mut spawns = vec![];
+    // the player starts at the world's origin
+    if inside(Vec2::ZERO) {
+        let start = tilemap.world_to_tile(Vec2::ZERO);
+        if !is_safe(tilemap.get(start)) {
+            tilemap.set(start, Some(params.path));
+        }
+        spawns.push(Spawn {
+            kind: SpawnKind::PlayerStart,
+            position: Vec2::ZERO,
+        });
+    }
+    // a share of the per map count, the fraction is a chance of one more
+    let tiles = (size * size) as f32 / (params.width * params.height) as f32;
+    let mut share = |count: u32| {
+        let expected = count as f32 * tiles;
+        return expected as u32 + rng.gen_bool(expected.fract() as f64) as u32;
+    };
+    let (dash_crystals, point_crystals, enemy_spawns) = (
+        share(params.dash_crystals),
+        share(params.point_crystals),
+        share(params.enemy_spawns),
+    );
+
+    let mut placed = 0;
+    let mut trails = 0;
+    while placed < dash_crystals && trails < dash_crystals.saturating_mul(TRAIL_ATTEMPTS) {
+        trails += 1;
+        let Some(tile) = pick_tile(&mut rng, params, &biomes, |biome| biome.decoration) else {
+            break;
+        };
+        let mut position = tilemap.tile_to_world(tile);
+        loop {
+            position += DIRECTIONS.choose(&mut rng).unwrap().normalize() * params.dash_trail_step;
+            // trails leaving the chunk or running into cliffs are cut short
+            if !inside(position) || !is_walkable(tilemap.get(tilemap.world_to_tile(position))) {
+                break;
+            }
+            spawns.push(Spawn {
+                kind: SpawnKind::DashCrystal,
+                position,
+            });
+            placed += 1;
+            if placed >= dash_crystals || !rng.gen_bool(params.dash_trail_chance) {
+                break;
+            }
+        }
+    }
+
+    // nothing to reach them from in an endless world, any walkable tile of a biome does
+    let walkable: Vec<IVec2> = biomes
+        .tiles()
+        .filter(|tile| biomes.get(*tile).is_some() && is_walkable(tilemap.get(*tile)))
+        .collect();
+    for tile in walkable.choose_multiple(&mut rng, point_crystals as usize) {
+        spawns.push(Spawn {
+            kind: SpawnKind::PointCrystal,
+            position: tilemap.tile_to_world(*tile),
+        });
+    }
+
+    for _ in 0..enemy_spawns {
+        let Some(tile) = pick_tile(&mut rng, params, &biomes, |biome| biome.enemy_density) else {
+            break;
+        };
+        if tilemap
+            .get(tile)
+            .map_or(false, |tile| tile.properties.walkable)
+        {
+            spawns.push(Spawn {
+                kind: SpawnKind::Enemy(None),
+                position: tilemap.tile_to_world(tile),
+            });
+        }
+    }
+
+    scatter_props(&mut rng, tilemap, params, &biomes, Some(area), &mut spawns);
+    return spawns;
+}
+
+impl GeneratedLevel {
+    // point crystals win the level so every one of them has to be collectable,
+    // props stay clear of everything that spawns
+    pub fn validate(self: &Self) -> Result<(), String> {
+        let start = self
+            .spawns
+            .iter()
+            .find(|spawn| matches!(spawn.kind, SpawnKind::PlayerStart))
+            .ok_or("no player start")?;
+        let reachable = reachable_from(
+            &self.tilemap,
+            self.tilemap.world_to_tile(start.position),
+            &blocked_by_props(&self.tilemap, &self.spawns),
+        );
+        let tile_size = self.tilemap.tile_width as f32;
+        let mut spawn_centers: HashMap<IVec2, Vec<Vec2>> = HashMap::new();
+        for spawn in self.spawns.iter() {
+            if !matches!(spawn.kind, SpawnKind::Prop(_)) {
+                let center = spawn.position + tile_size / 2.0;
+                spawn_centers
+                    .entry(self.tilemap.world_to_tile(center))
+                    .or_default()
+                    .push(center);
+            }
+        }
+        let mut point_crystals = 0;
+        for spawn in self.spawns.iter() {
+            let position = match spawn.kind {
+                SpawnKind::Prop(prop) => prop.foot(spawn.position),
+                _ => spawn.position,
+            };
+            let tile = self.tilemap.world_to_tile(position);
+            if !self.tilemap.contains(tile) {
+                return Err(format!(
+                    "{} at {} is outside the map",
+                    spawn.kind.name(),
+                    tile
+                ));
+            }
+            match spawn.kind {
+                SpawnKind::PointCrystal => {
+                    point_crystals += 1;
+                    if self.tilemap.get(tile).is_none() {
+                        return Err(format!("point crystal at {} is in water", tile));
+                    }
+                    if !reachable.contains(&tile) {
+                        return Err(format!("point crystal at {} can't be reached", tile));
+                    }
+                }
+                SpawnKind::Prop(prop) => {
+                    let blocking = (-1..=1)
+                        .flat_map(|x| (-1..=1).map(move |y| tile + IVec2::new(x, y)))
+                        .filter_map(|tile| spawn_centers.get(&tile))
+                        .flatten()
+                        .any(|center| center.distance(position) < tile_size);
+                    if blocking {
+                        return Err(format!("{} at {} is on top of a spawn", prop.name, tile));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if point_crystals == 0 {
+            return Err("no point crystals".to_string());
+        }
+        return Ok(());
+    }
+}
+
+// a random tile, tiles are kept with their biome's weight so rare biomes need more tries
+fn pick_tile(
+    rng: &mut StdRng,
+    params: &WorldgenParams,
+    biomes: &BiomeMap,
+    weight: impl Fn(&Biome) -> f32,
+) -> Option<IVec2> {
+    for _ in 0..PLACEMENT_ATTEMPTS {
+        let tile = biomes.origin
+            + IVec2::new(
+                rng.gen_range(0..biomes.width),
+                rng.gen_range(0..biomes.height),
+            );
+        let Some(biome) = biomes.get(tile) else {
+            continue;
+        };
+        if rng.gen::<f32>() < weight(&params.biomes[biome]) {
+            return Some(tile);
+        }
+    }
+    return None;
+}
+
+// dart throwing poisson-disc sampling, random points on the rule's tiles are kept when
+// they're far enough from every prop and spawn placed before them, with `bounds` (min, max)
+// solid props keep half their clearance inside them so neighbouring areas scattered on
+// their own can't wall anything off between them
+fn scatter_props(
+    rng: &mut StdRng,
+    tilemap: &Tilemap,
+    params: &WorldgenParams,
+    biomes: &BiomeMap,
+    bounds: Option<(Vec2, Vec2)>,
+    spawns: &mut Vec<Spawn>,
+) {
+    let tile_size = params.tile_size as f32;
+    // solid props keep a tile between their colliders so they never wall anything off
+    let clearance = |rule: &PropRule| {
+        return rule
+            .prop
+            .solid
+            .map_or(0.0, |solid| solid.max_element() + tile_size);
+    };
+    let radius = |rule: &PropRule| rule.spacing.max(clearance(rule)) / 2.0;
+    let max_radius = params
+        .biomes
+        .iter()
+        .flat_map(|biome| biome.props.iter())
+        .map(radius)
+        .fold(tile_size, f32::max);
+    // any two things that can be too close are in neighbouring cells
+    let cell_size = 2.0 * max_radius;
+    let cell = |position: Vec2| (position / cell_size).floor().as_ivec2();
+    let mut placed: HashMap<IVec2, Vec<(Vec2, f32)>> = HashMap::new();
+    // spawns keep a tile of clearance around their middle
+    for spawn in spawns.iter() {
+        let center = spawn.position + tile_size / 2.0;
+        placed
+            .entry(cell(center))
+            .or_default()
+            .push((center, tile_size));
+    }
+    for (index, biome) in params.biomes.iter().enumerate() {
+        for rule in biome.props.iter() {
+            let tiles: Vec<IVec2> = biomes
+                .tiles()
+                .filter(|tile| {
+                    biomes.get(*tile) == Some(index) && biome.allows(rule, tilemap.get(*tile))
+                })
+                .collect();
+            let radius = radius(rule);
+            let margin = clearance(rule) / 2.0;
+            let area = tiles.len() as f32 * tile_size * tile_size;
+            let darts = (area / (PI * radius * radius) * rule.density * PROP_DARTS) as u32;
+            for _ in 0..darts {
+                let Some(tile) = tiles.choose(rng) else {
+                    break;
+                };
+                // whole pixels so the foot lands back on the same tile from the sprite's corner
+                let offset = IVec2::new(
+                    rng.gen_range(0..params.tile_size as i32),
+                    rng.gen_range(0..params.tile_size as i32),
+                );
+                let foot = tilemap.tile_to_world(*tile) + offset.as_vec2();
+                if let Some((min, max)) = bounds {
+                    if foot.cmplt(min + margin).any() || foot.cmpgt(max - margin).any() {
+                        continue;
+                    }
+                }
+                let center = cell(foot);
+                let too_close = (-1..=1)
+                    .flat_map(|x| (-1..=1).map(move |y| center + IVec2::new(x, y)))
+                    .filter_map(|cell| placed.get(&cell))
+                    .flatten()
+                    .any(|(other, other_radius)| other.distance(foot) < radius + other_radius);
+                if too_close {
+                    continue;
+                }
+                placed.entry(center).or_default().push((foot, radius));
+                spawns.push(Spawn {
+                    kind: SpawnKind::Prop(rule.prop),
+                    position: foot - rule.prop.foot(Vec2::ZERO),
+                });
+            }
+        }
+    }
+}
+
+fn tile_by_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Tile>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    return tiled::find_tile(&name).map_err(serde::de::Error::custom);
+}
+
+fn solid_tile_by_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tile, D::Error> {
+    return tile_by_name(deserializer)?
+        .ok_or_else(|| serde::de::Error::custom("expected a tile, not water"));
+}
+
+fn tiles_by_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Option<Tile>>, D::Error> {
+    return Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| tiled::find_tile(name).map_err(serde::de::Error::custom))
+        .collect();
+}
+
+fn prop_by_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Prop, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    return prop::find(&name)
+        .ok_or_else(|| serde::de::Error::custom(format!("no prop named {}", name)));
+}
+
+fn is_walkable(tile: Option<&Tile>) -> bool {
+    return tile.map_or(render::WATER.walkable, |tile| tile.properties.walkable);
+}
+
+// walkable without hurting, the player start gets a path tile otherwise
+fn is_safe(tile: Option<&Tile>) -> bool {
+    return is_walkable(tile)
+        && tile.map_or(render::WATER.damage, |tile| tile.properties.damage) == 0;
+}
+
+// ties are broken by position so the result doesn't depend on the set's order
+fn closest<'a>(tiles: impl Iterator<Item = &'a IVec2>, target: IVec2) -> Option<IVec2> {
+    return tiles
+        .min_by_key(|tile| {
+            let offset = **tile - target;
+            (offset.dot(offset), tile.x, tile.y)
+        })
+        .copied();
+}
+
+// tiles the player can walk to from start with the same tile properties movement uses,
+// water is slow but walkable, cliffs and the outside of the map aren't
+fn reachable_from(tilemap: &Tilemap, start: IVec2, blocked: &HashSet<IVec2>) -> HashSet<IVec2> {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(tile) = stack.pop() {
+        if blocked.contains(&tile) || !tilemap.properties(tile).walkable {
+            continue;
+        }
+        if reachable.insert(tile) {
+            stack.extend(tilemap.neighbours(tile));
+        }
+    }
+    return reachable;
+}
+
+// tiles whose middle is under a solid prop's collider, props are smaller than a tile
+// so going around one on the same tile is left out
+fn blocked_by_props(tilemap: &Tilemap, spawns: &[Spawn]) -> HashSet<IVec2> {
+    let half_tile = Vec2::new(tilemap.tile_width as f32, tilemap.tile_height as f32) / 2.0;
+    let mut blocked = HashSet::new();
+    for spawn in spawns.iter() {
+        let SpawnKind::Prop(prop) = spawn.kind else {
+            continue;
+        };
+        let Some(size) = prop.solid else {
+            continue;
+        };
+        let foot = prop.foot(spawn.position);
+        let (min, max) = (
+            foot - Vec2::new(size.x / 2.0, size.y),
+            foot + Vec2::new(size.x / 2.0, 0.0),
+        );
+        blocked.extend(
+            tilemap
+                .region(tilemap.world_to_tile(min), tilemap.world_to_tile(max))
+                .filter(|tile| {
+                    let middle = tilemap.tile_to_world(*tile) + half_tile;
+                    middle.cmpge(min).all() && middle.cmple(max).all()
+                }),
+        );
+    }
+    return blocked;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: u64 = 300;
+    // in tiles, full size maps take most of a second each without optimizations
+    const TEST_SIZE: u32 = 32;
+
+    // the profile's noise, biomes and props on a smaller map, counts scaled by area
+    fn shrink(mut params: WorldgenParams) -> WorldgenParams {
+        let area = (TEST_SIZE * TEST_SIZE) as f32 / (params.width * params.height) as f32;
+        params.dash_crystals = (params.dash_crystals as f32 * area).ceil() as u32;
+        params.enemy_spawns = (params.enemy_spawns as f32 * area).ceil() as u32;
+        params.width = TEST_SIZE;
+        params.height = TEST_SIZE;
+        return params;
+    }
+
+    // every tile and spawn, enough to tell two levels apart
+    fn fingerprint(
+        level: &GeneratedLevel,
+    ) -> (Vec<Option<&'static str>>, Vec<(&'static str, Vec2)>) {
+        let tilemap = &level.tilemap;
+        let tiles = tilemap
+            .region(
+                IVec2::ZERO,
+                IVec2::new(tilemap.width() as i32, tilemap.height() as i32),
+            )
+            .map(|tile| tilemap.get(tile).map(|tile| tile.filename))
+            .collect();
+        let spawns = level
+            .spawns
+            .iter()
+            .map(|spawn| match spawn.kind {
+                SpawnKind::Prop(prop) => (prop.name, spawn.position),
+                kind => (kind.name(), spawn.position),
+            })
+            .collect();
+        return (tiles, spawns);
+    }
+
+    #[test]
+    fn every_profile_generates_valid_levels() {
+        let mut profiles: Vec<String> = fs::read_dir("res/worldgen")
+            .unwrap()
+            .map(|entry| entry.unwrap().path().to_string_lossy().into_owned())
+            .filter(|path| path.ends_with(".json"))
+            .collect();
+        profiles.sort();
+        assert!(!profiles.is_empty());
+        for profile in profiles.iter() {
+            let params = shrink(WorldgenParams::load(profile).unwrap());
+            for seed in 0..SEEDS {
+                let level = generate(seed, &params);
+                if let Err(error) = level.validate() {
+                    panic!("{} seed {}: {}", profile, seed, error);
+                }
+                // generating twice is the slow part, every tenth seed is enough
+                if seed % 10 != 0 {
+                    continue;
+                }
+                assert!(
+                    fingerprint(&level) == fingerprint(&generate(seed, &params)),
+                    "{} seed {} generated two different levels",
+                    profile,
+                    seed
+                );
+            }
+        }
+    }
+}